@@ -4,19 +4,56 @@ use clap::{Parser, ValueEnum};
 use std::path::PathBuf;
 use crate::solver;
 use crate::nfa;
+use crate::pdf::TEX_CMD;
+use shepherd::nfa::DOT_INIT_ID;
+use shepherd::ideal::Ideal;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum OutputFormat {
     Plain,
     Tex,
+    /// the LaTeX report compiled with the TeX engine of --tex-cmd, needs --output
+    Pdf,
     Csv,
+    /// a report with the automaton, the answer and the strategy as tables
+    Markdown,
+    /// a single tab-separated line on stdout with the answer and sizes, for batch runs
+    Summary,
+    /// the automaton itself, strategies have no GraphML representation
+    #[value(name = "graphml")]
+    GraphMl,
+}
+
+impl OutputFormat {
+    /// The extension of the files written with --output-dir.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Plain | OutputFormat::Summary => "txt",
+            OutputFormat::Tex => "tex",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Markdown => "md",
+            OutputFormat::GraphMl => "graphml",
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    #[arg(value_name = "AUTOMATON_FILE", help = "Path to the input")]
-    pub filename: String,
+    #[arg(
+        value_name = "AUTOMATON_FILE",
+        required_unless_present = "run_examples",
+        help = "Path to the input; with several paths, they are solved together and one summary line is printed for each."
+    )]
+    pub filenames: Vec<String>,
+
+    #[arg(
+        long = "run-examples",
+        hide = true,
+        help = "Solve the bundled examples and compare with their known answers."
+    )]
+    pub run_examples: bool,
 
     #[arg(
         short = 'f',
@@ -27,6 +64,14 @@ pub struct Args {
     )]
     pub input_format: nfa::InputFormat,
 
+    #[arg(
+        long = "dot-init-id",
+        value_name = "NODE_ID",
+        default_value = DOT_INIT_ID,
+        help = "The id of the artificial DOT node whose edges point to the initial states, e.g. __start0."
+    )]
+    pub dot_init_id: String,
+
     #[arg(
            short = 'v',
            long = "verbose",
@@ -60,6 +105,55 @@ pub struct Args {
     )]
     pub output_path: Option<PathBuf>,
 
+    #[arg(
+        long = "output-dir",
+        value_name = "DIR",
+        conflicts_with = "output_path",
+        help = "With several input files, write the output for each to DIR/<stem>.<ext>, the extension depending on --to. DIR is created if missing."
+    )]
+    pub output_dir: Option<PathBuf>,
+
+    #[arg(
+        long = "tex-cmd",
+        value_name = "TEX_CMD",
+        default_value = TEX_CMD,
+        help = "The TeX engine compiling the report with --to pdf, e.g. pdflatex or tectonic."
+    )]
+    pub tex_cmd: String,
+
+    #[arg(
+        long = "dump-semigroup",
+        value_name = "SEMIGROUP_FILE",
+        help = "Where to write the flow semigroup computed by the solver, as plain text, one flow after the other. There is no JSON dump, as there is no JSON output format."
+    )]
+    pub dump_semigroup: Option<PathBuf>,
+
+    #[arg(
+        long = "parse-only",
+        help = "Print the parsed automaton in the output format and exit without solving."
+    )]
+    pub parse_only: bool,
+
+    #[arg(
+        long = "accepting-regex",
+        value_name = "REGEX",
+        help = "Also mark as accepting every state whose name matches the regular expression."
+    )]
+    pub accepting_regex: Option<String>,
+
+    #[arg(
+        long = "complete",
+        help = "Send missing transitions to a non-accepting sink before solving."
+    )]
+    pub complete: bool,
+
+    #[arg(
+        long = "complete-with-self-loops",
+        conflicts_with = "complete",
+        help = "Let missing transitions loop on their source state before solving."
+    )]
+    pub complete_with_self_loops: bool,
+
     #[arg(
         short,
         long,
@@ -76,4 +170,83 @@ pub struct Args {
         help = "Solver output specification."
     )]
     pub solver_output: solver::SolverOutput,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "all-initial",
+        help = "Whether sheep start on all initial states or on any single one."
+    )]
+    pub source_semantics: solver::SourceSemantics,
+
+    #[arg(
+        long = "initial-config",
+        value_name = "CONFIG",
+        help = "Initial configuration, one comma-separated coefficient (a number or omega) per state, used instead of omega on the initial states."
+    )]
+    pub initial_config: Option<Ideal>,
+
+    #[arg(
+        long = "max-value",
+        value_name = "N",
+        value_parser = clap::value_parser!(u8).range(1..),
+        help = "Bound on finite coordinates used instead of the number of states. A smaller bound may under-approximate the winning strategy."
+    )]
+    pub max_value: Option<u8>,
+
+    #[arg(
+        long = "max-flows",
+        value_name = "MAX_FLOWS",
+        help = "Give up when a flow semigroup grows beyond this many flows."
+    )]
+    pub max_flows: Option<usize>,
+
+    #[arg(
+        long = "minimize-strategy",
+        help = "Drop letters and ideals of a winning strategy that are not needed from the initial states."
+    )]
+    pub minimize_strategy: bool,
+
+    #[arg(
+        long = "check-determinism-of-controller",
+        help = "Report whether the winning strategy ever allows two letters in the same configuration."
+    )]
+    pub check_determinism: bool,
+
+    #[arg(
+        long = "determinize-controller",
+        help = "Drop the ideals of a letter covered by a letter earlier in alphabetical order, unless the strategy would no longer win."
+    )]
+    pub determinize_controller: bool,
+
+    #[arg(
+        long = "deterministic",
+        help = "Accepted for compatibility, has no effect: the output is always deterministic, identical input gives byte-identical output."
+    )]
+    pub deterministic: bool,
+
+    #[arg(
+        long = "no-parallel",
+        help = "Solve on a single thread, for reproducible debugging. The answer is the same."
+    )]
+    pub no_parallel: bool,
+
+    #[arg(
+        long = "warn-on-nondeterminism",
+        help = "Warn about states with several successors on a letter before solving."
+    )]
+    pub warn_on_nondeterminism: bool,
+
+    #[arg(
+        long = "profile",
+        help = "Print on stderr the time spent parsing and in each phase of the solver."
+    )]
+    pub profile: bool,
+
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        help = "Print nothing; exit with 0 if controllable and 1 otherwise. Same as --solver-output quiet."
+    )]
+    pub quiet: bool,
 }
@@ -2,10 +2,16 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::iter::Sum;
 use std::ops::{Add, AddAssign, Sub};
+use std::str::FromStr;
 
 #[allow(non_camel_case_types)]
 pub type coef = u8;
 
+/// The raw value standing for omega in `Coef::as_coef`.
+/// Finite coefficients must stay strictly below it, otherwise
+/// they would be indistinguishable from omega once hashed.
+pub const OMEGA_SENTINEL: coef = coef::MAX;
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug, PartialOrd, Ord)]
 pub enum Coef {
     Value(coef),
@@ -19,6 +25,17 @@ impl Hash for Coef {
 }
 
 impl Coef {
+    /// Create a finite coefficient.
+    /// Debug builds reject the omega sentinel as a finite value.
+    pub fn new(value: coef) -> Coef {
+        debug_assert!(
+            value != OMEGA_SENTINEL,
+            "finite coefficient {} collides with the omega sentinel",
+            value
+        );
+        Coef::Value(value)
+    }
+
     pub(crate) fn round_up(&self, max_finite_value: coef) -> Coef {
         match self {
             Coef::Value(x) if *x > max_finite_value => Coef::Omega,
@@ -26,10 +43,65 @@ impl Coef {
         }
     }
 
+    /// Multiply by a population factor.
+    /// Omega stays omega; finite values saturate at the largest finite value,
+    /// just below `OMEGA_SENTINEL`.
+    pub fn scale(self, factor: coef) -> Coef {
+        match self {
+            Coef::Omega => Coef::Omega,
+            Coef::Value(x) => Coef::new(x.saturating_mul(factor).min(OMEGA_SENTINEL - 1)),
+        }
+    }
+
+    /// Sum of two coefficients, omega if the finite sum does not fit
+    /// below `OMEGA_SENTINEL`.
+    pub fn saturating_add(self, other: Coef) -> Coef {
+        match (self, other) {
+            (Coef::Value(x), Coef::Value(y)) => match x.checked_add(y) {
+                Some(sum) if sum < OMEGA_SENTINEL => Coef::Value(sum),
+                _ => Coef::Omega,
+            },
+            _ => Coef::Omega,
+        }
+    }
+
+    /// Smaller of two coefficients, omega being the top element.
+    /// Unlike `std::cmp::min`, it does not depend on the order of the enum variants.
+    pub fn min_of(a: Coef, b: Coef) -> Coef {
+        match (a, b) {
+            (Coef::Omega, c) | (c, Coef::Omega) => c,
+            (Coef::Value(x), Coef::Value(y)) => Coef::Value(x.min(y)),
+        }
+    }
+
+    /// Larger of two coefficients, omega being the top element.
+    pub fn max_of(a: Coef, b: Coef) -> Coef {
+        match (a, b) {
+            (Coef::Omega, _) | (_, Coef::Omega) => Coef::Omega,
+            (Coef::Value(x), Coef::Value(y)) => Coef::Value(x.max(y)),
+        }
+    }
+
+    /// Parse a single token: `_` or a number below the omega sentinel, `ω`, `w`, `omega`, `inf` or `∞`.
+    pub fn from_token(s: &str) -> Result<Coef, String> {
+        match s {
+            "_" => Ok(C0),
+            "ω" | "w" | "omega" | "inf" | "∞" => Ok(OMEGA),
+            s if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) => {
+                match s.parse::<coef>() {
+                    Ok(value) if value != OMEGA_SENTINEL => Ok(Coef::Value(value)),
+                    _ => Err(format!("'{}' is not a number below {}", s, OMEGA_SENTINEL)),
+                }
+            }
+            s => Err(format!("'{}' is neither omega nor a number", s)),
+        }
+    }
+
+    /// Raw value of the coefficient, omega being mapped to `OMEGA_SENTINEL`.
     pub fn as_coef(&self) -> coef {
         match self {
             Coef::Value(v) => *v,
-            Coef::Omega => coef::MAX, // associate 42 as the value of Omega
+            Coef::Omega => OMEGA_SENTINEL,
         }
     }
 }
@@ -49,7 +121,7 @@ impl Add for &Coef {
     fn add(self, other: Self) -> Self::Output {
         match (self, other) {
             (Coef::Omega, _) | (_, Coef::Omega) => OMEGA,
-            (Coef::Value(x), Coef::Value(y)) => Coef::Value(x + y),
+            (Coef::Value(x), Coef::Value(y)) => Coef::new(x + y),
         }
     }
 }
@@ -86,7 +158,7 @@ impl AddAssign for Coef {
     fn add_assign(&mut self, other: Self) {
         *self = match (*self, other) {
             (Coef::Omega, _) | (_, Coef::Omega) => Coef::Omega,
-            (Coef::Value(x0), Coef::Value(x1)) => Coef::Value(x0 + x1),
+            (Coef::Value(x0), Coef::Value(x1)) => Coef::new(x0 + x1),
         };
     }
 }
@@ -101,7 +173,7 @@ impl<'a> Sum<&'a Coef> for Coef {
             Coef::Omega => Err(Coef::Omega),
             Coef::Value(v) => Ok(sum + v),
         })
-        .map_or(Coef::Omega, Coef::Value)
+        .map_or(Coef::Omega, Coef::new)
     }
 }
 
@@ -115,7 +187,35 @@ impl Sum for Coef {
             Coef::Omega => Err(Coef::Omega),
             Coef::Value(v) => Ok(sum + v),
         })
-        .map_or(Coef::Omega, Coef::Value)
+        .map_or(Coef::Omega, Coef::new)
+    }
+}
+
+/// A finite coefficient, see `Coef::new` for the omega sentinel.
+impl From<coef> for Coef {
+    fn from(value: coef) -> Self {
+        Coef::new(value)
+    }
+}
+
+/// A finite coefficient, if the value is below the omega sentinel.
+impl TryFrom<u32> for Coef {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match coef::try_from(value) {
+            Ok(value) if value != OMEGA_SENTINEL => Ok(Coef::Value(value)),
+            _ => Err(format!("{} is not a number below {}", value, OMEGA_SENTINEL)),
+        }
+    }
+}
+
+/// Parses a number, or `omega`, with the spellings of `Coef::from_token`.
+impl FromStr for Coef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Coef::from_token(s.trim())
     }
 }
 
@@ -141,6 +241,85 @@ mod test {
         assert_eq!(OMEGA + OMEGA, OMEGA);
     }
 
+    #[test]
+    fn saturating_add() {
+        assert_eq!(C1.saturating_add(C1), Coef::Value(2));
+        assert_eq!(Coef::Value(200).saturating_add(Coef::Value(54)), Coef::Value(254));
+        assert_eq!(Coef::Value(200).saturating_add(Coef::Value(55)), OMEGA);
+        assert_eq!(Coef::Value(200).saturating_add(Coef::Value(100)), OMEGA);
+        assert_eq!(OMEGA.saturating_add(C0), OMEGA);
+    }
+
+    #[test]
+    fn min_max() {
+        assert_eq!(Coef::max_of(C2, OMEGA), OMEGA);
+        assert_eq!(Coef::max_of(OMEGA, C2), OMEGA);
+        assert_eq!(Coef::max_of(OMEGA, OMEGA), OMEGA);
+        assert_eq!(Coef::max_of(C1, C2), C2);
+        assert_eq!(Coef::min_of(C2, OMEGA), C2);
+        assert_eq!(Coef::min_of(OMEGA, C0), C0);
+        assert_eq!(Coef::min_of(OMEGA, OMEGA), OMEGA);
+        assert_eq!(Coef::min_of(C2, C1), C1);
+        // omega stays above the largest finite value
+        let largest = Coef::Value(OMEGA_SENTINEL - 1);
+        assert_eq!(Coef::max_of(largest, OMEGA), OMEGA);
+        assert_eq!(Coef::min_of(largest, OMEGA), largest);
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!("3".parse::<Coef>(), Ok(C3));
+        assert_eq!(" 0 ".parse::<Coef>(), Ok(C0));
+        assert_eq!("omega".parse::<Coef>(), Ok(OMEGA));
+        assert_eq!("ω".parse::<Coef>(), Ok(OMEGA));
+        assert_eq!("∞".parse::<Coef>(), Ok(OMEGA));
+        assert!("255".parse::<Coef>().is_err());
+        assert!("-1".parse::<Coef>().is_err());
+        assert!("a".parse::<Coef>().is_err());
+        assert_eq!("_".parse::<Coef>(), Ok(C0));
+    }
+
+    #[test]
+    fn conversions() {
+        let three: Coef = 3u8.into();
+        assert_eq!(three, C3);
+        assert_eq!(Coef::from(0), C0);
+        assert_eq!(Coef::try_from(2u32), Ok(C2));
+        assert_eq!(Coef::try_from(254u32), Ok(Coef::Value(254)));
+        assert!(Coef::try_from(OMEGA_SENTINEL as u32).is_err());
+        assert!(Coef::try_from(1000u32).is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "omega sentinel")]
+    fn from_omega_sentinel_panics() {
+        let _ = Coef::from(OMEGA_SENTINEL);
+    }
+
+    #[test]
+    fn from_token() {
+        assert_eq!(Coef::from_token("_"), Ok(C0));
+        assert_eq!(Coef::from_token("0"), Ok(C0));
+        assert_eq!(Coef::from_token("2"), Ok(C2));
+        assert_eq!(Coef::from_token("254"), Ok(Coef::Value(254)));
+        for omega in ["ω", "w", "omega", "inf", "∞"] {
+            assert_eq!(Coef::from_token(omega), Ok(OMEGA), "{}", omega);
+        }
+        for wrong in ["255", "1000", "+3", "-1", "", " 1", "W", "x"] {
+            assert!(Coef::from_token(wrong).is_err(), "{}", wrong);
+        }
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(C2.scale(3), Coef::Value(6));
+        assert_eq!(C2.scale(0), C0);
+        assert_eq!(OMEGA.scale(3), OMEGA);
+        assert_eq!(OMEGA.scale(0), OMEGA);
+        assert_eq!(Coef::Value(100).scale(3), Coef::Value(OMEGA_SENTINEL - 1));
+    }
+
     #[test]
     fn sum() {
         let vec = [C1, C1, C1];
@@ -151,6 +330,20 @@ mod test {
         assert_eq!(vec.iter().copied().sum::<Coef>(), OMEGA);
     }
 
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "omega sentinel")]
+    fn finite_value_rejects_omega_sentinel() {
+        let _ = Coef::new(OMEGA_SENTINEL);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "omega sentinel")]
+    fn sum_reaching_omega_sentinel_panics() {
+        let _ = Coef::Value(OMEGA_SENTINEL - 1) + C1;
+    }
+
     #[test]
     fn cmp() {
         assert!(C1 < OMEGA);
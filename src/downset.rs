@@ -7,7 +7,7 @@ use itertools::Itertools;
 use log::debug;
 use once_cell::sync::Lazy;
 use rayon::prelude::*;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::sync::Mutex;
 use std::{collections::HashSet, vec::Vec};
@@ -88,19 +88,51 @@ impl DownSet {
         DownSet(w.iter().map(|&v| Ideal::from_vec(v.to_vec())).collect())
     }
 
+    /// The dimension shared by all ideals, None if the downset is empty.
+    pub fn dimension(&self) -> Option<usize> {
+        self.0.iter().next().map(|ideal| ideal.dimension())
+    }
+
     /// Check if an ideal is included in the downward-closed set.
     pub fn contains(&self, source: &Ideal) -> bool {
         self.0.iter().any(|x| source <= x)
     }
 
+    /// Check if every ideal of the slice is included in the downward-closed set.
+    pub fn contains_all(&self, ideals: &[Ideal]) -> bool {
+        ideals.iter().all(|ideal| self.contains(ideal))
+    }
+
     /// Check if the downset is contained in another downset.
     pub fn is_contained_in(&self, other: &DownSet) -> bool {
         self.0.iter().all(|x| other.contains(x))
     }
 
+    /// The downward-closed set of configurations in either downset, minimized.
+    pub fn union(&self, other: &DownSet) -> DownSet {
+        assert!(
+            self.dimension().is_none()
+                || other.dimension().is_none()
+                || self.dimension() == other.dimension(),
+            "Union of downsets of dimensions {:?} and {:?}",
+            self.dimension(),
+            other.dimension()
+        );
+        let mut result = self.clone();
+        result.insert_all(other.ideals());
+        result.minimize();
+        result
+    }
+
     /// Insert an ideal in the downward-closed set.
     /// The method returns true if the downset has changed, and false if the ideal was already in the downset.
     pub fn insert(&mut self, ideal: &Ideal) -> bool {
+        debug_assert!(
+            self.dimension().is_none_or(|dim| dim == ideal.dimension()),
+            "Inserting an ideal of dimension {} in a downset of dimension {:?}",
+            ideal.dimension(),
+            self.dimension()
+        );
         if self.0.contains(ideal) {
             false
         } else {
@@ -109,11 +141,47 @@ impl DownSet {
         }
     }
 
+    /// Insert several ideals in the downward-closed set.
+    /// An ideal already covered by the downset is skipped,
+    /// otherwise the ideals it dominates are removed before it is added.
+    /// The method returns the number of ideals that were actually new.
+    pub fn insert_all<'a, I: IntoIterator<Item = &'a Ideal>>(&mut self, ideals: I) -> usize {
+        let mut added = 0;
+        for ideal in ideals {
+            if self.contains(ideal) {
+                continue;
+            }
+            debug_assert!(self.dimension().is_none_or(|dim| dim == ideal.dimension()));
+            self.0.retain(|x| !x.is_below(ideal));
+            self.0.insert(ideal.clone());
+            added += 1;
+        }
+        added
+    }
+
     /// Get an iterator over the ideals of the downset.
     pub fn ideals(&self) -> impl Iterator<Item = &Ideal> {
         self.0.iter()
     }
 
+    /// Get an iterator over the ideals of the downset,
+    /// sorted lexicographically by their coefficients, omega coming last.
+    /// Unlike `ideals`, the order does not depend on the hashing.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = &Ideal> {
+        self.0
+            .iter()
+            .sorted_by(|x, y| x.cmp_canonical(y))
+    }
+
+    /// The maximal ideal closest to `target` for `Ideal::distance`, with that distance,
+    /// the first in canonical order on ties. None if the downset is empty.
+    /// If `target` is not in the downset, this tells how many sheep are in excess, and where.
+    pub fn nearest(&self, target: &Ideal) -> Option<(&Ideal, u64)> {
+        self.iter_sorted()
+            .map(|ideal| (ideal, ideal.distance(target)))
+            .min_by_key(|&(_, distance)| distance)
+    }
+
     /// Compute the intersection of the downset set with another ideal.
     /// The method returns true if the downward-closed set has changed.
     /// The method is used in the solver to restrict the set of possible configurations.
@@ -144,14 +212,18 @@ impl DownSet {
                 new_ideals.insert(ideal);
             } else {
                 changed = true;
-                for other_ideal in &other.0 {
-                    new_ideals.insert(&Ideal::intersection(ideal, other_ideal));
-                }
+                let intersections: Vec<Ideal> = other
+                    .0
+                    .iter()
+                    .map(|other_ideal| Ideal::intersection(ideal, other_ideal))
+                    .collect();
+                new_ideals.insert_all(&intersections);
             }
         }
         if changed {
             new_ideals.minimize();
             self.0 = new_ideals.0;
+            self.assert_antichain();
         }
         changed
     }
@@ -179,9 +251,7 @@ impl DownSet {
                 changed = true;
                 let safe = Self::safe_post(ideal, edges, safe_target, max_finite_value);
                 debug!("restricted to\n{}", safe);
-                for other_ideal in safe.ideals() {
-                    new_ideals.insert(other_ideal);
-                }
+                new_ideals.insert_all(safe.ideals());
             }
         }
         if changed {
@@ -200,6 +270,10 @@ impl DownSet {
     /// The method is used in the solver to compute the set of configurations from which it is safe to play an action.
     /// The method returns the set of configurations which are safe to play.
     ///
+    /// Finite coordinates of the result never exceed `maximal_finite_coordinate`:
+    /// the result is exact on configurations bounded by it, provided the finite coordinates
+    /// of the downward-closed set are bounded by it too, and may miss larger configurations otherwise.
+    ///
     /// # Examples
     /// ```
     /// use shepherd::coef::{coef, C0, C1, C2, OMEGA};
@@ -314,6 +388,7 @@ impl DownSet {
             });
         result.minimize();
         //println!("result {}\n", result);
+        result.assert_antichain();
         result
     }
 
@@ -479,15 +554,29 @@ impl DownSet {
         self.0.is_empty()
     }
 
-    fn get_image(
+    /// Check that no stored ideal is strictly below another one, as ensured by `minimize`.
+    pub fn is_antichain(&self) -> bool {
+        self.0.iter().all(|x| !self.0.iter().any(|y| x < y))
+    }
+
+    /// Panics in debug builds if the stored ideals do not form an antichain.
+    pub(crate) fn assert_antichain(&self) {
+        debug_assert!(self.is_antichain(), "Not an antichain:\n{}", self);
+    }
+
+    pub(crate) fn get_image(
         dim: usize,
         dom: &Ideal,
         edges: &crate::graph::Graph,
         max_finite_value: coef,
     ) -> DownSet {
         let mut downset = DownSet::new();
+        // successors are sorted so that the cache key does not depend on the edges' hash order
         let choices = (0..dom.dimension())
-            .map(|index| get_choices(dim, dom.get(index), edges.get_successors(index)))
+            .map(|index| {
+                let successors = edges.get_successors(index).into_iter().sorted().collect();
+                get_choices(dim, dom.get(index), successors)
+            })
             .collect::<Vec<_>>();
         for im in choices
             .iter()
@@ -513,18 +602,35 @@ impl DownSet {
     }
 
     /// Removes ideal with precision >.
+    /// If some ideal was rounded, the downset is minimized afterwards,
+    /// since rounding may make an ideal dominate another.
     pub fn round_down(&mut self, maximal_finite_value: coef, dim: usize) {
-        let to_remove: Vec<Ideal> = self
+        //ideals are moved out and back, none is cloned
+        let mut rounded = false;
+        for mut ideal in std::mem::take(&mut self.0) {
+            rounded |= ideal.round_down(maximal_finite_value, dim);
+            self.0.insert(ideal);
+        }
+        if rounded {
+            self.minimize();
+            self.assert_antichain();
+        }
+    }
+
+    /// Replaces by omega every finite coordinate larger than `max_finite_value`,
+    /// the dual of `round_down`: the downset can only grow.
+    /// Ideals dominated by a rounded one are removed.
+    pub fn round_up(&mut self, max_finite_value: coef) {
+        self.0 = self
             .0
             .iter()
-            .filter(|s| s.some_finite_coordinate_is_larger_than(maximal_finite_value))
-            .cloned()
+            .map(|ideal| {
+                let mut rounded = ideal.clone();
+                rounded.round_up_in_place(max_finite_value);
+                rounded
+            })
             .collect();
-        for mut ideal in to_remove {
-            self.0.remove(&ideal);
-            ideal.round_down(maximal_finite_value, dim);
-            self.0.insert(ideal);
-        }
+        self.minimize();
     }
 
     fn is_safe(
@@ -539,10 +645,67 @@ impl DownSet {
         result
     }
 
+    /// Keep only the ideals satisfying the predicate, then minimize.
+    pub fn retain<F: Fn(&Ideal) -> bool>(&mut self, pred: F) {
+        self.0.retain(|ideal| pred(ideal));
+        self.minimize();
+    }
+
+    /// The least ideal containing every ideal of the downset,
+    /// or None if the downset is empty.
+    pub fn bounding_box(&self) -> Option<Ideal> {
+        self.0
+            .iter()
+            .cloned()
+            .reduce(|acc, ideal| Ideal::join(&acc, &ideal))
+    }
+
+    /// For every coordinate, the number of ideals by their coefficient at that coordinate.
+    /// Empty if the downset is empty.
+    pub fn coordinate_histogram(&self) -> Vec<HashMap<Coef, usize>> {
+        let mut histogram = vec![HashMap::new(); self.dimension().unwrap_or(0)];
+        for ideal in self.0.iter() {
+            for (counts, &c) in histogram.iter_mut().zip(ideal.iter()) {
+                *counts.entry(c).or_insert(0) += 1;
+            }
+        }
+        histogram
+    }
+
+    /// Reorder the coordinates of every ideal:
+    /// coordinate `i` of the result is coordinate `perm[i]` of the original.
+    /// Panics if `perm` is not a bijection of the coordinates.
+    pub fn permute(&self, perm: &[usize]) -> DownSet {
+        let mut seen = vec![false; perm.len()];
+        for &i in perm {
+            assert!(
+                i < perm.len() && !seen[i],
+                "{:?} is not a permutation",
+                perm
+            );
+            seen[i] = true;
+        }
+        debug_assert!(self.0.iter().all(|ideal| ideal.dimension() == perm.len()));
+        self.project(perm)
+    }
+
+    /// Project every ideal on the coordinates in `keep`, in that order;
+    /// other coordinates are dropped.
+    pub fn project(&self, keep: &[usize]) -> DownSet {
+        let mut result = DownSet(
+            self.0
+                .iter()
+                .map(|ideal| Ideal::from_vec(keep.iter().map(|&i| ideal.get(i)).collect()))
+                .collect(),
+        );
+        result.minimize();
+        result
+    }
+
     // create a CSV representation of this downward-closed set
     pub fn as_csv(&self) -> Vec<String> {
         let mut lines: Vec<String> = Vec::new();
-        for s in &self.0 {
+        for s in self.iter_sorted() {
             lines.push(s.as_csv());
         }
         lines
@@ -582,9 +745,7 @@ impl fmt::Display for DownSet {
         if self.is_empty() {
             writeln!(f, "empty downward-closed set")
         } else {
-            let mut vec: Vec<String> = self.0.iter().map(|x| x.to_string()).collect();
-            vec.sort();
-            writeln!(f, "\t{}", vec.join("\n\t"))
+            writeln!(f, "\t{}", self.iter_sorted().join("\n\t"))
         }
     }
 }
@@ -593,7 +754,48 @@ impl fmt::Display for DownSet {
 mod test {
 
     use super::*;
-    use crate::coef::{C0, C1, C2, OMEGA};
+    use crate::coef::{C0, C1, C2, C3, OMEGA};
+    use crate::graph::Graph;
+
+    #[test]
+    fn is_antichain() {
+        let mut downset = DownSet::from_vecs(&[&[C2, C0], &[C1, C0], &[C0, OMEGA]]);
+        assert!(!downset.is_antichain());
+        downset.minimize();
+        assert!(downset.is_antichain());
+        downset.assert_antichain();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Not an antichain")]
+    fn assert_antichain_fails() {
+        DownSet::from_vecs(&[&[C2, C0], &[C1, C0]]).assert_antichain();
+    }
+
+    #[test]
+    fn coordinate_histogram() {
+        let downset = DownSet::from_vecs(&[&[OMEGA, C1, C2], &[C1, C1, OMEGA]]);
+        assert_eq!(
+            downset.coordinate_histogram(),
+            vec![
+                HashMap::from([(OMEGA, 1), (C1, 1)]),
+                HashMap::from([(C1, 2)]),
+                HashMap::from([(C2, 1), (OMEGA, 1)]),
+            ]
+        );
+        assert!(DownSet::from_vecs(&[]).coordinate_histogram().is_empty());
+    }
+
+    #[test]
+    fn union() {
+        let left = DownSet::from_vecs(&[&[C2, C0], &[C0, C1]]);
+        let right = DownSet::from_vecs(&[&[C1, C0], &[C0, OMEGA]]);
+        let union = left.union(&right);
+        assert_eq!(union, DownSet::from_vecs(&[&[C2, C0], &[C0, OMEGA]]));
+        assert_eq!(union, right.union(&left));
+        assert_eq!(left.union(&DownSet::from_vecs(&[])), left);
+    }
 
     #[test]
     fn is_in_ideal() {
@@ -672,6 +874,213 @@ mod test {
         assert!(downset0.is_empty());
     }
 
+    #[test]
+    fn contains_all() {
+        let downset = DownSet::from_vecs(&[&[C1, OMEGA, C0], &[C2, C0, C1]]);
+        let inside = [
+            Ideal::from_vec(vec![C1, C2, C0]),
+            Ideal::from_vec(vec![C0, OMEGA, C0]),
+            Ideal::from_vec(vec![C2, C0, C1]),
+        ];
+        assert!(downset.contains_all(&inside));
+        assert!(downset.contains_all(&[]));
+        let mut mixed = inside.to_vec();
+        mixed.push(Ideal::from_vec(vec![C2, C1, C0]));
+        assert!(!downset.contains_all(&mixed));
+        assert!(!DownSet::from_vecs(&[]).contains_all(&inside[..1]));
+    }
+
+    #[test]
+    fn insert_all() {
+        let mut downset = DownSet::from_vecs(&[&[C1, C1, C0], &[C0, C0, OMEGA]]);
+        let added = downset.insert_all(&[
+            // new, dominates [1, 1, 0]
+            Ideal::from_vec(vec![C2, C1, C0]),
+            // duplicate
+            Ideal::from_vec(vec![C0, C0, OMEGA]),
+            // dominated
+            Ideal::from_vec(vec![C1, C0, C0]),
+            // new, incomparable
+            Ideal::from_vec(vec![C0, C2, C1]),
+            // dominated by an ideal added earlier in the same call
+            Ideal::from_vec(vec![C2, C0, C0]),
+        ]);
+        assert_eq!(added, 2);
+        assert_eq!(
+            downset,
+            DownSet::from_vecs(&[&[C2, C1, C0], &[C0, C0, OMEGA], &[C0, C2, C1]])
+        );
+        assert_eq!(downset.insert_all(&[]), 0);
+    }
+
+    #[test]
+    fn iter_sorted() {
+        let ideals = [
+            Ideal::from_vec(vec![OMEGA, C0, C0]),
+            Ideal::from_vec(vec![C2, C1, C0]),
+            Ideal::from_vec(vec![C2, C0, OMEGA]),
+            Ideal::from_vec(vec![C0, OMEGA, C1]),
+        ];
+        let downset = DownSet::from_vec(&ideals);
+        let sorted = downset.iter_sorted().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            vec![
+                ideals[3].clone(),
+                ideals[2].clone(),
+                ideals[1].clone(),
+                ideals[0].clone()
+            ]
+        );
+        // the order does not depend on how the set was built
+        let rebuilt = DownSet::from_vec(&ideals.iter().rev().cloned().collect::<Vec<_>>());
+        assert!(rebuilt.iter_sorted().eq(sorted.iter()));
+        assert_eq!(rebuilt.to_string(), downset.to_string());
+        assert_eq!(rebuilt.as_csv(), downset.as_csv());
+    }
+
+    #[test]
+    fn retain() {
+        let mut downset = DownSet::from_vecs(&[
+            &[OMEGA, OMEGA, OMEGA],
+            &[C2, OMEGA, C0],
+            &[C1, C1, OMEGA],
+        ]);
+        downset.retain(|ideal| !ideal.is_top());
+        assert_eq!(
+            downset,
+            DownSet::from_vecs(&[&[C2, OMEGA, C0], &[C1, C1, OMEGA]])
+        );
+    }
+
+    #[test]
+    fn bounding_box() {
+        let ideal0 = Ideal::from_vec(vec![C0, C1, C2, OMEGA]);
+        let ideal1 = Ideal::from_vec(vec![OMEGA, C2, C1, C0]);
+        let downset = DownSet::from_vec(&[ideal0.clone(), ideal1.clone()]);
+        let bounding_box = downset.bounding_box().unwrap();
+        assert!(downset.ideals().all(|ideal| ideal <= &bounding_box));
+        assert_eq!(bounding_box, Ideal::join(&ideal0, &ideal1));
+        assert_eq!(DownSet::from_vecs(&[]).bounding_box(), None);
+    }
+
+    #[test]
+    fn permute_then_inverse() {
+        let downset = DownSet::from_vecs(&[&[C0, C1, C2, OMEGA], &[OMEGA, C2, C1, C0]]);
+        let perm = [2, 0, 3, 1];
+        let mut inverse = [0; 4];
+        for (i, &j) in perm.iter().enumerate() {
+            inverse[j] = i;
+        }
+        let permuted = downset.permute(&perm);
+        assert_eq!(
+            permuted,
+            DownSet::from_vecs(&[&[C2, C0, OMEGA, C1], &[C1, OMEGA, C0, C2]])
+        );
+        assert_eq!(permuted.permute(&inverse), downset);
+    }
+
+    #[test]
+    #[should_panic]
+    fn permute_rejects_non_bijection() {
+        let downset = DownSet::from_vecs(&[&[C0, C1, C2]]);
+        downset.permute(&[0, 0, 1]);
+    }
+
+    #[test]
+    fn project() {
+        let downset = DownSet::from_vecs(&[&[C0, C1, C2, OMEGA], &[C1, C2, C1, C0]]);
+        assert_eq!(
+            downset.project(&[3, 1]),
+            DownSet::from_vecs(&[&[OMEGA, C1], &[C0, C2]])
+        );
+        assert_eq!(
+            downset.project(&[1]),
+            DownSet::from_vecs(&[&[C2]])
+        );
+    }
+
+    //every configuration reachable in one step from an explicit configuration,
+    //none if some sheep has no successor
+    fn images(config: &[coef], edges: &Graph) -> Vec<Vec<coef>> {
+        let mut result = vec![vec![0; config.len()]];
+        for (i, &n) in config.iter().enumerate().filter(|&(_, &n)| n > 0) {
+            let succ = edges.get_successors(i);
+            let moves = partitions::get_partitions(n, succ.len());
+            result = result
+                .iter()
+                .cartesian_product(moves.iter())
+                .map(|(image, moved)| {
+                    let mut image = image.clone();
+                    for (&j, &m) in succ.iter().zip(moved.iter()) {
+                        image[j] += m;
+                    }
+                    image
+                })
+                .collect();
+        }
+        result
+    }
+
+    // The safe pre-image of D contains exactly the explicit configurations
+    // all of whose images are in D, as long as the coordinates of the configurations
+    // and the finite coordinates of D are at most the bound.
+    // Above the bound, finite coordinates of the pre-image are capped:
+    // see pre_image_rounding_gap.
+    #[test]
+    fn pre_image_matches_explicit_images() {
+        let bound: coef = 2;
+        let graphs = [
+            Graph::from_vec(3, vec![(0, 1), (0, 2), (1, 1), (2, 2)]),
+            Graph::from_vec(3, vec![(0, 0), (0, 1), (1, 2), (2, 0)]),
+            Graph::from_vec(3, vec![(0, 1), (1, 2)]),
+            Graph::from_vec(3, vec![(0, 1), (0, 2), (1, 0), (1, 2), (2, 2)]),
+        ];
+        let downsets = [
+            DownSet::from_vecs(&[&[C0, C1, C1]]),
+            DownSet::from_vecs(&[&[C0, OMEGA, C0]]),
+            DownSet::from_vecs(&[&[C0, C2, C0], &[C0, C0, C2]]),
+            DownSet::from_vecs(&[&[C1, OMEGA, C1], &[OMEGA, C0, C2]]),
+            DownSet::from_vecs(&[&[OMEGA, OMEGA, C0]]),
+        ];
+        let configurations = (0..3).map(|_| 0..=bound).multi_cartesian_product();
+        for (edges, downset) in graphs.iter().cartesian_product(downsets.iter()) {
+            let pre_image = downset.safe_pre_image(edges, bound);
+            for config in configurations.clone() {
+                let ideal = Ideal::from_vec(config.iter().map(|&n| Coef::Value(n)).collect());
+                let images = images(&config, edges);
+                let expected = !images.is_empty()
+                    && images.iter().all(|image| {
+                        downset.contains(&Ideal::from_vec(
+                            image.iter().map(|&n| Coef::Value(n)).collect(),
+                        ))
+                    });
+                assert_eq!(
+                    pre_image.contains(&ideal),
+                    expected,
+                    "{:?} by {} in {}",
+                    config,
+                    edges,
+                    downset
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pre_image_rounding_gap() {
+        // the three sheep of 0 all go to 1, where three are allowed,
+        // but finite coordinates of the pre-image never exceed the bound
+        let edges = Graph::from_vec(2, vec![(0, 1), (1, 1)]);
+        let downset = DownSet::from_vecs(&[&[C0, Coef::Value(3)]]);
+        let pre_image = downset.safe_pre_image(&edges, 2);
+        assert!(pre_image.contains(&Ideal::from_vec(vec![C2, C0])));
+        assert!(!pre_image.contains(&Ideal::from_vec(vec![Coef::Value(3), C0])));
+        assert!(downset
+            .safe_pre_image(&edges, 3)
+            .contains(&Ideal::from_vec(vec![Coef::Value(3), C0])));
+    }
+
     //test issafe
     #[test]
     fn is_safe() {
@@ -869,4 +1278,58 @@ mod test {
         let pre_image0 = downset0.safe_pre_image(&edges, dim as coef);
         assert_eq!(pre_image0, DownSet::from_vecs(&[&[C2, C0, C0, C0, C0]]));
     }
+
+    #[test]
+    fn dimension() {
+        let mut downset = DownSet::new();
+        assert_eq!(downset.dimension(), None);
+        downset.insert(&Ideal::from_vec(vec![C1, C0, OMEGA]));
+        assert_eq!(downset.dimension(), Some(3));
+        // a shorter ideal is not covered by a longer one
+        assert!(!downset.contains(&Ideal::from_vec(vec![C1])));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn insert_dimension_mismatch() {
+        let mut downset = DownSet::from_vecs(&[&[C1, C0]]);
+        downset.insert(&Ideal::from_vec(vec![C1]));
+    }
+
+    #[test]
+    fn nearest() {
+        let downset = DownSet::from_vecs(&[&[C2, C0, C0], &[C0, C1, OMEGA], &[C1, C1, C1]]);
+        assert_eq!(DownSet::from_vec(&[]).nearest(&Ideal::new(3, C0)), None);
+        // one sheep too many on the first state
+        let target = Ideal::from_vec(vec![C3, C0, C0]);
+        assert_eq!(
+            downset.nearest(&target),
+            Some((&Ideal::from_vec(vec![C2, C0, C0]), 1))
+        );
+        let target = Ideal::from_vec(vec![C0, C2, OMEGA]);
+        assert_eq!(
+            downset.nearest(&target),
+            Some((&Ideal::from_vec(vec![C0, C1, OMEGA]), 1))
+        );
+        // ties go to the first ideal in canonical order
+        let downset = DownSet::from_vecs(&[&[C1, C0], &[C0, C1]]);
+        assert_eq!(
+            downset.nearest(&Ideal::new(2, C0)),
+            Some((&Ideal::from_vec(vec![C0, C1]), 1))
+        );
+    }
+
+    #[test]
+    fn round_up() {
+        // (3, 1) becomes (ω, 1) which dominates (4, 0)
+        let mut downset = DownSet::from_vecs(&[&[C3, C1], &[Coef::Value(4), C0], &[C1, C2]]);
+        downset.round_up(2);
+        assert_eq!(downset.ideals().count(), 2);
+        assert_eq!(downset, DownSet::from_vecs(&[&[OMEGA, C1], &[C1, C2]]));
+        // nothing above the bound
+        let mut unchanged = downset.clone();
+        unchanged.round_up(2);
+        assert_eq!(unchanged, downset);
+    }
 }
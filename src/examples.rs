@@ -0,0 +1,24 @@
+//! Example automata bundled with the crate, with their known answers.
+//! Used by the integration tests and by the `--run-examples` smoke test.
+
+pub const EXAMPLE1: &str = include_str!("../examples/bottleneck-1-ab.tikz");
+pub const EXAMPLE1_COMPLETE: &str = include_str!("../examples/bottleneck-1-ab-complete.tikz");
+pub const EXAMPLE2: &str = include_str!("../examples/bottleneck-2.tikz");
+pub const EXAMPLE_BUG12: &str = include_str!("../examples/bug12.tikz");
+pub const CONTROLLABLE: &str = r"
+\begin{tikzpicture}
+	\node[state, initial] at (0,0) (A) {$0$};
+	\node[state,accepting] at (2,0) (B) {$1$};
+	\path[->]
+	(A) edge [] node {$a$} (B)
+    ;
+\end{tikzpicture}
+";
+
+/// Name, tikz content and expected controllability of every example with a known answer.
+pub const EXAMPLES: &[(&str, &str, bool)] = &[
+    ("bottleneck-1-ab.tikz", EXAMPLE1, false),
+    ("bottleneck-1-ab-complete.tikz", EXAMPLE1_COMPLETE, false),
+    ("bottleneck-2.tikz", EXAMPLE2, false),
+    ("controllable", CONTROLLABLE, true),
+];
@@ -3,13 +3,17 @@ use crate::graph::Graph;
 use crate::ideal::Ideal;
 use crate::partitions;
 use itertools::Itertools;
+use log::warn;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::iter::Sum;
 use std::ops::{Add, AddAssign, Mul, Sub};
-use std::{collections::HashSet, vec::Vec}; // Import the itertools crate for multi_cartesian_product
+use std::{collections::HashMap, collections::HashSet, vec::Vec}; // Import the itertools crate for multi_cartesian_product
 pub type Domain = Vec<Coef>;
 
+/// Above that many lines for a single coefficient, computing flows is likely to blow up.
+const PARTITION_WARNING_THRESHOLD: u128 = 100_000;
+
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub struct Flow {
     pub nb_rows: usize,
@@ -165,6 +169,62 @@ impl Flow {
         }
     }
 
+    /// The flow with no edges.
+    pub fn zero(dim: usize) -> Flow {
+        Flow {
+            nb_rows: dim,
+            nb_cols: dim,
+            entries: vec![C0; dim * dim],
+        }
+    }
+
+    /// The neutral element of the product:
+    /// omega on the diagonal and 0 elsewhere,
+    /// since the product takes the max over paths of the min along each path.
+    pub fn identity(dim: usize) -> Flow {
+        let mut result = Flow::zero(dim);
+        for i in 0..dim {
+            result.entries[i * dim + i] = OMEGA;
+        }
+        result
+    }
+
+    /// The flow with `value` on the edges of the graph and 0 elsewhere.
+    /// With omega, this is the only flow of the all-omega domain along the graph.
+    pub fn from_graph(graph: &Graph, value: Coef) -> Flow {
+        let dim = graph.dim();
+        let mut result = Flow::zero(dim);
+        for &(i, j) in graph.iter() {
+            result.entries[i * dim + j] = value;
+        }
+        result
+    }
+
+    /// The flow induced by a subset of states: entry (k, l) is the entry of self
+    /// between `states[k]` and `states[l]`.
+    /// Panics if a state is out of range or appears twice.
+    pub fn subflow(&self, states: &[usize]) -> Flow {
+        assert!(
+            states.iter().all(|&i| i < self.nb_rows && i < self.nb_cols),
+            "State out of range in {:?}",
+            states
+        );
+        assert!(states.iter().all_unique(), "Repeated state in {:?}", states);
+        let dim = states.len();
+        Flow {
+            nb_rows: dim,
+            nb_cols: dim,
+            entries: states
+                .iter()
+                .flat_map(|i| states.iter().map(move |j| self.get(i, j)))
+                .collect(),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.entries.iter().all(|&c| c == C0)
+    }
+
     pub fn is_square(&self) -> bool {
         self.nb_rows == self.nb_cols
     }
@@ -173,7 +233,7 @@ impl Flow {
         let entries = &self.entries;
         let other_entries = &other.entries;
         let dim = self.nb_rows;
-        let mut result: Vec<Coef> = vec![C0; dim * dim];
+        let mut result = Flow::zero(dim);
         //not idiomatic but fast
         let mut k = 0;
         for i in 0..dim {
@@ -186,7 +246,7 @@ impl Flow {
                 let mut is_omega = false;
                 //more effcicient than the idiomatic stream
                 for _l in 0..dim {
-                    let c = std::cmp::min(entries[li], other_entries[lj]);
+                    let c = Coef::min_of(entries[li], other_entries[lj]);
                     match c {
                         Coef::Value(x) => {
                             resultk = std::cmp::max(resultk, x);
@@ -199,7 +259,7 @@ impl Flow {
                         }
                     }
                 }
-                result[k] = if is_omega {
+                result.entries[k] = if is_omega {
                     OMEGA
                 } else {
                     Coef::Value(resultk)
@@ -207,11 +267,7 @@ impl Flow {
                 k += 1;
             }
         }
-        Flow {
-            nb_rows: dim,
-            nb_cols: dim,
-            entries: result,
-        }
+        result
     }
     /*
     pub(crate) fn expand(dim: usize, transports: &Vec<(&Vec<usize>, Flow, &Vec<usize>)>) -> Flow {
@@ -268,7 +324,7 @@ impl Flow {
         debug_assert!(right.is_square());
         debug_assert_eq!(left.nb_rows, right.nb_rows);
         let dim = left.nb_rows;
-        let mut entries: Vec<Coef> = vec![C0; dim * dim];
+        let mut result = Flow::zero(dim);
         let mut k = 0;
         //set omega components
         for i in 0..dim {
@@ -279,7 +335,7 @@ impl Flow {
                 //more effcicient than the idiomatic stream
                 for _l in 0..dim {
                     if left.entries[li] == OMEGA && right.entries[lj] == OMEGA {
-                        entries[k] = OMEGA;
+                        result.entries[k] = OMEGA;
                         break;
                     } else {
                         li += 1;
@@ -289,16 +345,31 @@ impl Flow {
                 k += 1;
             }
         }
-        Flow {
-            nb_rows: dim,
-            nb_cols: dim,
-            entries,
-        }
+        result
     }
 
     pub fn iteration(&self) -> Flow {
+        self.iteration_from(self.idempotent())
+    }
+
+    /// Same as `iteration`, with the idempotent powers already computed kept in `idempotents`,
+    /// since the same flows recur during a semigroup computation.
+    pub(crate) fn iteration_cached(&self, idempotents: &mut HashMap<Flow, Flow>) -> Flow {
+        let idempotent = match idempotents.get(self) {
+            Some(idempotent) => idempotent.clone(),
+            None => {
+                let idempotent = self.idempotent();
+                idempotents.insert(self.clone(), idempotent.clone());
+                idempotent
+            }
+        };
+        self.iteration_from(idempotent)
+    }
+
+    //the iteration, given the idempotent power of the flow
+    fn iteration_from(&self, idempotent: Flow) -> Flow {
         let dim = self.nb_rows;
-        let mut result: Flow = self.idempotent();
+        let mut result: Flow = idempotent;
         for s0 in 0..dim {
             for t0 in 0..dim {
                 if self.is_1(&s0, &t0) {
@@ -359,8 +430,38 @@ impl Flow {
             .collect()
     }
 
-    //iteration of a fl
-    fn idempotent(&self) -> Flow {
+    /// n-fold product of the flow with itself, by fast exponentiation.
+    /// The power 0 is the identity.
+    pub fn power(&self, n: usize) -> Flow {
+        debug_assert!(self.is_square());
+        let mut result = Flow::identity(self.nb_rows);
+        let mut square = self.clone();
+        let mut n = n;
+        while n > 0 {
+            if n % 2 == 1 {
+                result = &result * &square;
+            }
+            n /= 2;
+            if n > 0 {
+                square = &square * &square;
+            }
+        }
+        result
+    }
+
+    /// The least k >= 1 such that the k-th power of the flow is idempotent.
+    pub fn stabilization_exponent(&self) -> usize {
+        let mut power = self.clone();
+        let mut k = 1;
+        while !power.is_idempotent() {
+            power = &power * self;
+            k += 1;
+        }
+        k
+    }
+
+    //the idempotent power of the flow, by repeated squaring
+    pub(crate) fn idempotent(&self) -> Flow {
         let mut result = self.clone();
         loop {
             let result_squared = &result * &result;
@@ -388,6 +489,19 @@ impl Flow {
         self.entries[i * self.nb_cols + j] == OMEGA
     }
 
+    /// Which entries are omega, in row-major order.
+    pub fn omega_pattern(&self) -> Vec<bool> {
+        self.entries.iter().map(|&c| c == OMEGA).collect()
+    }
+
+    /// The (row, column) positions of the omega entries, in row-major order.
+    pub fn omega_positions(&self) -> Vec<(usize, usize)> {
+        (0..self.nb_rows)
+            .flat_map(|i| (0..self.nb_cols).map(move |j| (i, j)))
+            .filter(|(i, j)| self.is_omega(i, j))
+            .collect()
+    }
+
     fn get_lines_vec(domain: &Ideal, edges: &Graph) -> Vec<Vec<Domain>> {
         let dim = domain.dimension();
         domain
@@ -402,6 +516,17 @@ impl Flow {
 
     //todo cache results
     fn get_lines(out: &[usize], coef: &Coef, dim: usize) -> Vec<Domain> {
+        if let Coef::Value(x) = *coef {
+            let count = partitions::partition_count(x as u16, out.len());
+            if count > PARTITION_WARNING_THRESHOLD {
+                warn!(
+                    "Splitting {} sheep among {} successors gives {} flow lines",
+                    x,
+                    out.len(),
+                    count
+                );
+            }
+        }
         match *coef {
             C0 => vec![vec![C0; dim]],
             OMEGA => vec![(0..dim)
@@ -463,6 +588,36 @@ impl Flow {
     }
 }
 
+impl Flow {
+    /// Same as the `Display` output, but every column is right-aligned
+    /// to its widest entry, so that large flows stay readable.
+    pub fn to_string_aligned(&self) -> String {
+        if self.is_empty() {
+            return "empty flow".to_string();
+        }
+        let tokens: Vec<String> = self.entries.iter().map(|c| c.to_string()).collect();
+        let widths: Vec<usize> = (0..self.nb_cols)
+            .map(|j| {
+                (0..self.nb_rows)
+                    .map(|i| tokens[i * self.nb_cols + j].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let mut result = String::new();
+        for row in tokens.chunks(self.nb_cols) {
+            let row = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(token, &width)| format!("{:>width$}", token, width = width))
+                .collect::<Vec<_>>()
+                .join(" , ");
+            result.push_str(&format!("( {} )\n", row));
+        }
+        result
+    }
+}
+
 impl fmt::Display for Flow {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut result = String::new();
@@ -486,6 +641,20 @@ mod test {
     use super::*;
     use crate::coef::{C0, C1, C2, C3};
 
+    #[test]
+    fn to_string_aligned() {
+        let flow = Flow::from_entries(
+            3,
+            3,
+            &[Coef::Value(12), C0, OMEGA, C1, Coef::Value(200), C0, OMEGA, C2, C3],
+        );
+        let aligned = flow.to_string_aligned();
+        let lengths = aligned.lines().map(|l| l.chars().count()).collect::<Vec<_>>();
+        assert_eq!(lengths.len(), 3);
+        assert!(lengths.iter().all(|&l| l == lengths[0]), "{}", aligned);
+        assert_eq!(aligned.lines().next(), Some("( 12 ,   _ , ω )"));
+    }
+
     impl Flow {
         //used for tests
         #[allow(dead_code)]
@@ -510,6 +679,105 @@ mod test {
         }
     }
 
+    #[test]
+    fn omega_pattern() {
+        let flow = Flow::from_lines(&[&[OMEGA, C1, C0], &[C0, C2, OMEGA]]);
+        assert_eq!(
+            flow.omega_pattern(),
+            vec![true, false, false, false, false, true]
+        );
+        assert_eq!(flow.omega_positions(), vec![(0, 0), (1, 2)]);
+        assert!(Flow::zero(2).omega_positions().is_empty());
+    }
+
+    #[test]
+    fn power() {
+        let flow = Flow::from_lines(&[&[C2, C1, C0], &[C0, C1, C3], &[OMEGA, C0, C1]]);
+        assert_eq!(flow.power(0), Flow::identity(3));
+        assert_eq!(flow.power(1), flow);
+        assert_eq!(flow.power(3), &(&flow * &flow) * &flow);
+        assert_eq!(flow.power(6), &flow.power(3) * &flow.power(3));
+    }
+
+    #[test]
+    fn stabilization_exponent() {
+        // 0 -> 1 -> 2: the square only keeps 0 -> 2, the cube is zero
+        let nilpotent = Flow::from_lines(&[&[C0, C1, C0], &[C0, C0, C1], &[C0, C0, C0]]);
+        assert!(!nilpotent.power(2).is_zero());
+        assert!(nilpotent.power(3).is_zero());
+        assert_eq!(nilpotent.stabilization_exponent(), 3);
+
+        let idempotent = Flow::from_lines(&[&[OMEGA, C0], &[C0, OMEGA]]);
+        assert!(idempotent.is_idempotent());
+        assert_eq!(idempotent.stabilization_exponent(), 1);
+    }
+
+    #[test]
+    fn zero_and_identity() {
+        let flows = [
+            Flow::from_lines(&[&[OMEGA, C1, C0], &[OMEGA, C0, C1], &[C0, C0, OMEGA]]),
+            Flow::from_lines(&[&[C2, C0, C3], &[C0, C1, C0], &[OMEGA, C0, C0]]),
+            Flow::zero(3),
+        ];
+        let identity = Flow::identity(3);
+        assert!(!identity.is_zero());
+        assert!(Flow::zero(3).is_zero());
+        for f in flows.iter() {
+            assert_eq!(&(&identity * f), f);
+            assert_eq!(&(f * &identity), f);
+            assert!((&Flow::zero(3) * f).is_zero());
+        }
+    }
+
+    #[test]
+    fn subflow() {
+        let flow = Flow::from_lines(&[
+            &[C1, C0, C2, C0],
+            &[C0, OMEGA, C0, C3],
+            &[C0, C0, C0, C0],
+            &[C2, C0, C1, OMEGA],
+        ]);
+        assert_eq!(
+            flow.subflow(&[1, 3]),
+            Flow::from_lines(&[&[OMEGA, C3], &[C0, OMEGA]])
+        );
+        // the order of the states is kept
+        assert_eq!(
+            flow.subflow(&[3, 0]),
+            Flow::from_lines(&[&[OMEGA, C2], &[C0, C1]])
+        );
+        assert_eq!(flow.subflow(&[0, 1, 2, 3]), flow);
+        assert_eq!(flow.subflow(&[]), Flow::zero(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn subflow_out_of_range() {
+        Flow::identity(2).subflow(&[0, 2]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn subflow_repeated_state() {
+        Flow::identity(3).subflow(&[1, 1]);
+    }
+
+    #[test]
+    fn from_graph() {
+        let edges = Graph::from_vec(3, vec![(0, 1), (1, 0), (1, 1), (2, 1), (2, 2)]);
+        let flow = Flow::from_graph(&edges, OMEGA);
+        assert_eq!(
+            Flow::from_domain_and_edges(&Ideal::new(3, OMEGA), &edges),
+            HashSet::from([flow.clone()])
+        );
+        assert_eq!(flow.omega_positions(), vec![(0, 1), (1, 0), (1, 1), (2, 1), (2, 2)]);
+        assert_eq!(
+            Flow::from_graph(&edges, C2),
+            Flow::from_lines(&[&[C0, C2, C0], &[C2, C2, C0], &[C0, C2, C2]])
+        );
+        assert!(Flow::from_graph(&Graph::from_vec(2, vec![]), OMEGA).is_zero());
+    }
+
     #[test]
     #[should_panic]
     fn from_domain_and_edges_panic_case() {
@@ -700,6 +968,41 @@ mod test {
         assert_eq!(flow.iteration(), expected);
     }
 
+    #[test]
+    fn cached_iteration() {
+        let flows = [
+            Flow::from_entries(2, 2, &[OMEGA, C1, C0, OMEGA]),
+            Flow::from_lines(&[
+                &[OMEGA, OMEGA, C0, C0],
+                &[C0, C0, C1, C0],
+                &[C0, C0, C0, OMEGA],
+                &[C0, C0, C0, OMEGA],
+            ]),
+            Flow::from_lines(&[
+                &[OMEGA, OMEGA, C0, C0],
+                &[C0, OMEGA, C1, C0],
+                &[C0, C0, C0, OMEGA],
+                &[C0, C0, C0, OMEGA],
+            ]),
+            Flow::from_lines(&[
+                &[OMEGA, OMEGA, C0, C0],
+                &[C0, OMEGA, C1, C0],
+                &[C0, C0, OMEGA, OMEGA],
+                &[C0, C0, C0, OMEGA],
+            ]),
+        ];
+        let mut idempotents = HashMap::new();
+        for flow in flows.iter() {
+            assert_eq!(flow.iteration_cached(&mut idempotents), flow.iteration());
+        }
+        assert_eq!(idempotents.len(), flows.len());
+        // the second round only reads the cache
+        for flow in flows.iter() {
+            assert_eq!(flow.iteration_cached(&mut idempotents), flow.iteration());
+        }
+        assert_eq!(idempotents.len(), flows.len());
+    }
+
     //tests preimage
     #[test]
     fn pre_image() {
@@ -1,4 +1,6 @@
-use std::{collections::HashSet, fmt};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct SubGraph(Vec<Option<usize>>);
@@ -24,6 +26,29 @@ impl Graph {
         Graph { dim, edges }
     }
 
+    /// Create a graph from its adjacency matrix, `matrix[i][j]` holds iff there is an edge (i, j).
+    /// Panics if the matrix is not square.
+    pub fn from_adjacency(matrix: &[Vec<bool>]) -> Graph {
+        let dim = matrix.len();
+        assert!(
+            matrix.iter().all(|row| row.len() == dim),
+            "Adjacency matrix is not square"
+        );
+        let edges = (0..dim)
+            .flat_map(|i| (0..dim).filter_map(move |j| matrix[i][j].then_some((i, j))))
+            .collect();
+        Graph { dim, edges }
+    }
+
+    /// Return the adjacency matrix of the graph, of size dim x dim.
+    pub fn to_adjacency_matrix(&self) -> Vec<Vec<bool>> {
+        let mut matrix = vec![vec![false; self.dim]; self.dim];
+        for &(i, j) in self.edges.iter() {
+            matrix[i][j] = true;
+        }
+        matrix
+    }
+
     /// Return an iterator over the edges of the graph.
     pub fn iter(&self) -> impl Iterator<Item = &(usize, usize)> {
         self.edges.iter()
@@ -37,9 +62,148 @@ impl Graph {
             .collect()
     }
 
+    /// Return the predecessors of a node.
+    pub fn get_predecessors(&self, j: usize) -> Vec<usize> {
+        self.edges
+            .iter()
+            .filter_map(|&(i0, j0)| (j == j0).then_some(i0))
+            .collect()
+    }
+
+    /// Return the number of successors of every node.
+    pub fn out_degrees(&self) -> Vec<usize> {
+        self.degrees().0
+    }
+
+    /// Return the number of predecessors of every node.
+    pub fn in_degrees(&self) -> Vec<usize> {
+        self.degrees().1
+    }
+
+    /// Return the out- and in-degrees of every node, in a single scan of the edges.
+    pub fn degrees(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut out_degrees = vec![0; self.dim];
+        let mut in_degrees = vec![0; self.dim];
+        for &(i, j) in self.edges.iter() {
+            out_degrees[i] += 1;
+            in_degrees[j] += 1;
+        }
+        (out_degrees, in_degrees)
+    }
+
+    /// Return the set of nodes reachable from some source, sources included.
+    pub fn reachable_from(&self, sources: &[usize]) -> HashSet<usize> {
+        self.closure(sources, |i| self.get_successors(i))
+    }
+
+    /// Return the set of nodes from which some target is reachable, targets included.
+    pub fn coreachable_to(&self, targets: &[usize]) -> HashSet<usize> {
+        self.closure(targets, |j| self.get_predecessors(j))
+    }
+
+    //breadth-first closure of a set of nodes under a neighbour relation
+    fn closure<F>(&self, start: &[usize], neighbours: F) -> HashSet<usize>
+    where
+        F: Fn(usize) -> Vec<usize>,
+    {
+        let mut visited: HashSet<usize> = start.iter().cloned().collect();
+        let mut to_process: VecDeque<usize> = start.iter().cloned().collect();
+        while let Some(node) = to_process.pop_front() {
+            for next in neighbours(node) {
+                if visited.insert(next) {
+                    to_process.push_back(next);
+                }
+            }
+        }
+        visited
+    }
+
+    /// Return the connected components of the graph with edges taken in both directions.
+    /// Each component is sorted and components are ordered by their smallest node;
+    /// an isolated node is a component on its own.
+    pub fn weakly_connected_components(&self) -> Vec<Vec<usize>> {
+        //union-find with path halving
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        let mut parent: Vec<usize> = (0..self.dim).collect();
+        for &(i, j) in self.edges.iter() {
+            let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+            parent[ri.max(rj)] = ri.min(rj);
+        }
+        let mut components: Vec<Vec<usize>> = Vec::new();
+        let mut index: Vec<Option<usize>> = vec![None; self.dim];
+        for node in 0..self.dim {
+            let root = find(&mut parent, node);
+            match index[root] {
+                Some(k) => components[k].push(node),
+                None => {
+                    index[root] = Some(components.len());
+                    components.push(vec![node]);
+                }
+            }
+        }
+        components
+    }
+
     pub fn dim(&self) -> usize {
         self.dim
     }
+
+    /// Return the graph whose edges are those of self or of other.
+    pub fn union(&self, other: &Graph) -> Graph {
+        assert_eq!(self.dim, other.dim, "Graphs of different dimensions");
+        Graph {
+            dim: self.dim,
+            edges: self.edges.union(&other.edges).cloned().collect(),
+        }
+    }
+
+    /// Return the graph with every edge (i, j) replaced by (j, i).
+    pub fn reversed(&self) -> Graph {
+        Graph {
+            dim: self.dim,
+            edges: self.edges.iter().map(|&(i, j)| (j, i)).collect(),
+        }
+    }
+
+    /// Return the relational composition: an edge (i, k) for every edge (i, j) of self
+    /// followed by an edge (j, k) of other.
+    pub fn compose(&self, other: &Graph) -> Graph {
+        assert_eq!(self.dim, other.dim, "Graphs of different dimensions");
+        let edges = self
+            .edges
+            .iter()
+            .flat_map(|&(i, j)| other.get_successors(j).into_iter().map(move |k| (i, k)))
+            .collect();
+        Graph {
+            dim: self.dim,
+            edges,
+        }
+    }
+}
+
+/// Graphs are equal if they have the same dimension and the same edges.
+impl PartialEq for Graph {
+    fn eq(&self, other: &Self) -> bool {
+        self.dim == other.dim && self.edges == other.edges
+    }
+}
+
+impl Eq for Graph {}
+
+/// Edges are hashed in sorted order, the same whatever order they were inserted in.
+impl Hash for Graph {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.dim.hash(state);
+        let mut edges: Vec<&(usize, usize)> = self.edges.iter().collect();
+        edges.sort();
+        edges.hash(state);
+    }
 }
 
 impl fmt::Display for Graph {
@@ -49,3 +213,140 @@ impl fmt::Display for Graph {
         write!(f, "\n\t{}", vec.join("\n\t"))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn edges(graph: &Graph) -> HashSet<(usize, usize)> {
+        graph.iter().cloned().collect()
+    }
+
+    fn hash_of(graph: &Graph) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        graph.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn eq_and_hash() {
+        let a = Graph::new(3, &[(0, 1), (1, 2), (2, 0), (2, 2)]);
+        let b = Graph::from_vec(3, vec![(2, 2), (2, 0), (1, 2), (0, 1)]);
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert_eq!(HashSet::from([a.clone(), b]).len(), 1);
+        // same edges in a larger graph
+        assert_ne!(a, Graph::new(4, &[(0, 1), (1, 2), (2, 0), (2, 2)]));
+        assert_ne!(a, Graph::new(3, &[(0, 1), (1, 2), (2, 0)]));
+    }
+
+    #[test]
+    fn degrees() {
+        let graph = Graph::new(4, &[(0, 1), (0, 2), (2, 2), (1, 2), (0, 1)]);
+        assert_eq!(graph.out_degrees(), vec![2, 1, 1, 0]);
+        assert_eq!(graph.in_degrees(), vec![0, 1, 3, 0]);
+        assert_eq!(graph.degrees(), (graph.out_degrees(), graph.in_degrees()));
+    }
+
+    #[test]
+    fn weakly_connected_components() {
+        let connected = Graph::new(4, &[(0, 1), (2, 1), (3, 2)]);
+        assert_eq!(connected.weakly_connected_components(), vec![vec![0, 1, 2, 3]]);
+
+        let two = Graph::new(5, &[(3, 0), (4, 1), (1, 4), (3, 3)]);
+        assert_eq!(
+            two.weakly_connected_components(),
+            vec![vec![0, 3], vec![1, 4], vec![2]]
+        );
+        assert!(Graph::new(0, &[]).weakly_connected_components().is_empty());
+    }
+
+    #[test]
+    fn union() {
+        let a = Graph::new(3, &[(0, 1), (1, 2)]);
+        let b = Graph::new(3, &[(1, 2), (2, 0)]);
+        assert_eq!(edges(&a.union(&b)), HashSet::from([(0, 1), (1, 2), (2, 0)]));
+        assert_eq!(a.union(&b).dim(), 3);
+    }
+
+    #[test]
+    fn reversed() {
+        let graph = Graph::new(3, &[(0, 1), (0, 2), (2, 2)]);
+        let reversed = graph.reversed();
+        assert_eq!(edges(&reversed), HashSet::from([(1, 0), (2, 0), (2, 2)]));
+        assert_eq!(edges(&reversed.reversed()), edges(&graph));
+        for i in 0..3 {
+            let mut successors = reversed.get_successors(i);
+            let mut predecessors = graph.get_predecessors(i);
+            successors.sort();
+            predecessors.sort();
+            assert_eq!(successors, predecessors);
+        }
+        assert_eq!(graph.reachable_from(&[0]), reversed.coreachable_to(&[0]));
+    }
+
+    #[test]
+    fn compose() {
+        // 0 -a-> 1, 0 -a-> 2 then 1 -b-> 0, 2 -b-> 2
+        let a = Graph::new(3, &[(0, 1), (0, 2)]);
+        let b = Graph::new(3, &[(1, 0), (2, 2)]);
+        assert_eq!(edges(&a.compose(&b)), HashSet::from([(0, 0), (0, 2)]));
+        assert_eq!(edges(&b.compose(&a)), HashSet::from([(1, 1), (1, 2)]));
+
+        let identity = Graph::new(3, &[(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(edges(&a.compose(&identity)), edges(&a));
+        assert_eq!(edges(&identity.compose(&a)), edges(&a));
+    }
+
+    #[test]
+    #[should_panic]
+    fn compose_dimension_mismatch() {
+        Graph::new(2, &[(0, 1)]).compose(&Graph::new(3, &[(1, 2)]));
+    }
+
+    #[test]
+    fn adjacency_round_trip() {
+        let graph = Graph::new(3, &[(0, 1), (1, 1), (2, 0)]);
+        let matrix = graph.to_adjacency_matrix();
+        assert_eq!(
+            matrix,
+            vec![
+                vec![false, true, false],
+                vec![false, true, false],
+                vec![true, false, false]
+            ]
+        );
+        let back = Graph::from_adjacency(&matrix);
+        assert_eq!(back.dim(), 3);
+        assert_eq!(edges(&back), edges(&graph));
+        assert!(Graph::from_adjacency(&[]).to_adjacency_matrix().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_adjacency_not_square() {
+        Graph::from_adjacency(&[vec![true, false], vec![false]]);
+    }
+
+    #[test]
+    fn reachable_dag() {
+        // 0 -> 1 -> 3, 0 -> 2, 4 isolated
+        let graph = Graph::new(5, &[(0, 1), (1, 3), (0, 2)]);
+        assert_eq!(graph.reachable_from(&[0]), HashSet::from([0, 1, 2, 3]));
+        assert_eq!(graph.reachable_from(&[1]), HashSet::from([1, 3]));
+        assert_eq!(graph.reachable_from(&[4]), HashSet::from([4]));
+        assert_eq!(graph.coreachable_to(&[3]), HashSet::from([0, 1, 3]));
+        assert_eq!(graph.coreachable_to(&[2, 4]), HashSet::from([0, 2, 4]));
+    }
+
+    #[test]
+    fn reachable_cycle() {
+        // 0 -> 1 -> 2 -> 0, 2 -> 3
+        let graph = Graph::new(4, &[(0, 1), (1, 2), (2, 0), (2, 3)]);
+        assert_eq!(graph.reachable_from(&[1]), HashSet::from([0, 1, 2, 3]));
+        assert_eq!(graph.reachable_from(&[3]), HashSet::from([3]));
+        assert_eq!(graph.coreachable_to(&[0]), HashSet::from([0, 1, 2]));
+        assert_eq!(graph.coreachable_to(&[3]), HashSet::from([0, 1, 2, 3]));
+        assert!(graph.reachable_from(&[]).is_empty());
+    }
+}
@@ -1,15 +1,25 @@
-use crate::coef::{coef, Coef, OMEGA};
-use std::cmp::min;
+use crate::coef::{coef, Coef, C0, OMEGA};
+use crate::downset::DownSet;
+use crate::graph::Graph;
 use std::fmt;
 use std::iter::Sum;
 use std::ops::{Add, AddAssign};
+use std::str::FromStr;
 use std::vec::Vec;
 
+/// Contribution to `Ideal::distance` of a coordinate which is omega on one side only,
+/// larger than any sum of finite differences.
+pub const OMEGA_DISTANCE: u64 = 1 << 32;
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct Ideal(Vec<Coef>);
 
 impl PartialOrd for Ideal {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        //ideals of different dimensions are incomparable, zip would truncate the longer one
+        if self.dimension() != other.dimension() {
+            return None;
+        }
         let is_smaller_or_equal = self.0.iter().zip(other.0.iter()).all(|(x, y)| x <= y);
         let is_greater_or_equal = other.0.iter().zip(self.0.iter()).all(|(x, y)| x <= y);
         match (is_smaller_or_equal, is_greater_or_equal) {
@@ -114,13 +124,68 @@ impl Ideal {
         self.0[state] = val;
     }
 
+    /// Sum over the coordinates of the absolute differences,
+    /// a coordinate which is omega on one side only counts for `OMEGA_DISTANCE`.
+    pub fn distance(&self, other: &Ideal) -> u64 {
+        debug_assert_eq!(self.dimension(), other.dimension());
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(&x, &y)| match (x, y) {
+                (Coef::Omega, Coef::Omega) => 0,
+                (Coef::Omega, _) | (_, Coef::Omega) => OMEGA_DISTANCE,
+                (Coef::Value(x), Coef::Value(y)) => x.abs_diff(y) as u64,
+            })
+            .sum()
+    }
+
     pub fn intersection(x: &Ideal, ideal: &Ideal) -> Ideal {
         debug_assert_eq!(x.dimension(), ideal.dimension());
         Ideal(
             x.0.iter()
                 .zip(ideal.0.iter())
-                .map(|(x, y)| min(x, y))
-                .cloned()
+                .map(|(&x, &y)| Coef::min_of(x, y))
+                .collect(),
+        )
+    }
+
+    /// Lower every coordinate to the one of `bound` if it is larger,
+    /// omega in the bound does not cap. Same as `intersection`, in place.
+    pub fn clamp_to(&mut self, bound: &Ideal) {
+        assert_eq!(self.dimension(), bound.dimension(), "Clamping to a box of another dimension");
+        for (x, &b) in self.0.iter_mut().zip(bound.0.iter()) {
+            *x = Coef::min_of(*x, b);
+        }
+    }
+
+    /// True if other <= self and other != self in the domination order.
+    pub fn dominates_strictly(&self, other: &Ideal) -> bool {
+        other < self
+    }
+
+    /// A total order on ideals, used to sort them deterministically:
+    /// lexicographic on the raw coefficients, omega being the largest.
+    /// It extends the domination order of `PartialOrd`.
+    pub fn cmp_canonical(&self, other: &Ideal) -> std::cmp::Ordering {
+        self.0
+            .iter()
+            .map(|x| x.as_coef())
+            .cmp(other.0.iter().map(|x| x.as_coef()))
+    }
+
+    /// Multiply every coordinate by a population factor, see `Coef::scale`.
+    pub fn scale(&self, factor: coef) -> Ideal {
+        Ideal(self.0.iter().map(|x| x.scale(factor)).collect())
+    }
+
+    /// Coordinatewise maximum of two ideals,
+    /// the least ideal containing both.
+    pub fn join(x: &Ideal, ideal: &Ideal) -> Ideal {
+        debug_assert_eq!(x.dimension(), ideal.dimension());
+        Ideal(
+            x.0.iter()
+                .zip(ideal.0.iter())
+                .map(|(&x, &y)| Coef::max_of(x, y))
                 .collect(),
         )
     }
@@ -139,6 +204,43 @@ impl Ideal {
         Ideal(result)
     }
 
+    /// Returns the number of omega coordinates.
+    pub fn count_omega(&self) -> usize {
+        self.0.iter().filter(|&&x| x == OMEGA).count()
+    }
+
+    /// Returns the sum of the coordinates, or None if some coordinate is omega.
+    pub fn finite_sum(&self) -> Option<u64> {
+        self.0.iter().try_fold(0u64, |sum, &x| match x {
+            Coef::Omega => None,
+            Coef::Value(v) => Some(sum + v as u64),
+        })
+    }
+
+    /// The configurations the sheep of this one may reach in one step along `edges`,
+    /// each sheep following some edge from its state.
+    /// Finite coordinates above `max_value` become omega.
+    /// Empty if some state holding sheep has no outgoing edge.
+    pub fn image_under(&self, edges: &Graph, max_value: coef) -> DownSet {
+        assert_eq!(self.dimension(), edges.dim(), "Ideal and graph of different dimensions");
+        if (0..self.dimension()).any(|i| self.get(i) != C0 && edges.get_successors(i).is_empty()) {
+            return DownSet::from_vec(&[]);
+        }
+        let mut image = DownSet::get_image(edges.dim(), self, edges, max_value);
+        image.minimize();
+        image
+    }
+
+    /// Whether every coordinate is omega.
+    pub fn is_top(&self) -> bool {
+        self.0.iter().all(|&x| x == OMEGA)
+    }
+
+    /// Whether every coordinate is zero.
+    pub fn is_bottom(&self) -> bool {
+        self.0.iter().all(|&x| x == C0)
+    }
+
     pub fn all_omega(&self, succ: &[usize]) -> bool {
         succ.iter().all(|&i| self.get(i) == OMEGA)
     }
@@ -152,14 +254,33 @@ impl Ideal {
         )
     }
 
-    pub fn round_down(&mut self, upper_bound: coef, dim: usize) {
+    /// Replaces every finite coordinate larger than max_finite_value by omega.
+    /// Returns true if the ideal changed.
+    pub fn round_up_in_place(&mut self, max_finite_value: coef) -> bool {
+        let mut changed = false;
+        for x in self.0.iter_mut() {
+            let rounded = x.round_up(max_finite_value);
+            if rounded != *x {
+                *x = rounded;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Lowers every finite coordinate larger than upper_bound to upper_bound.
+    /// Returns true if the ideal changed.
+    pub fn round_down(&mut self, upper_bound: coef, dim: usize) -> bool {
+        let mut changed = false;
         for i in 0..dim {
             if let Coef::Value(x) = self.get(i) {
                 if x > upper_bound {
                     self.set(i, Coef::Value(upper_bound));
+                    changed = true;
                 }
             }
         }
+        changed
     }
 
     pub fn some_finite_coordinate_is_larger_than(&self, upper_bound: coef) -> bool {
@@ -211,6 +332,21 @@ impl Ideal {
     }
 }
 
+/// Parses a comma-separated list of coefficients, e.g. `2, 0, omega`.
+impl FromStr for Ideal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("empty configuration".to_string());
+        }
+        s.split(',')
+            .map(|x| x.parse::<Coef>())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Ideal)
+    }
+}
+
 impl fmt::Display for Ideal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let content = self
@@ -226,10 +362,10 @@ impl fmt::Display for Ideal {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::coef::C0;
     use crate::coef::C1;
     use crate::coef::C2;
     use crate::coef::OMEGA;
+    use crate::coef::OMEGA_SENTINEL;
 
     #[allow(clippy::neg_cmp_op_on_partial_ord)]
     #[test]
@@ -260,6 +396,18 @@ mod test {
         assert!(final_ideal <= final_ideal);
     }
 
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            "2, 0,omega".parse::<Ideal>(),
+            Ok(Ideal::from_vec(vec![C2, C0, OMEGA]))
+        );
+        assert_eq!("ω".parse::<Ideal>(), Ok(Ideal::from_vec(vec![OMEGA])));
+        assert!("".parse::<Ideal>().is_err());
+        assert!("1,,2".parse::<Ideal>().is_err());
+        assert!("1;2".parse::<Ideal>().is_err());
+    }
+
     #[test]
     fn min() {
         let ideal0 = Ideal::from_vec(vec![C0, C1, C2, OMEGA]);
@@ -268,10 +416,167 @@ mod test {
         assert_eq!(intersect, Ideal::intersection(&ideal0, &ideal1));
     }
 
+    #[test]
+    fn count_omega_and_finite_sum() {
+        let all_omega = Ideal::new(3, OMEGA);
+        assert_eq!(all_omega.count_omega(), 3);
+        assert_eq!(all_omega.finite_sum(), None);
+
+        let mixed = Ideal::from_vec(vec![C2, OMEGA, C1, C0]);
+        assert_eq!(mixed.count_omega(), 1);
+        assert_eq!(mixed.finite_sum(), None);
+
+        let finite = Ideal::from_vec(vec![Coef::Value(200), Coef::Value(200), C1]);
+        assert_eq!(finite.count_omega(), 0);
+        assert_eq!(finite.finite_sum(), Some(401));
+    }
+
+    #[test]
+    fn clamp_to() {
+        let mut ideal = Ideal::from_vec(vec![OMEGA, OMEGA, C1, OMEGA]);
+        let bound = Ideal::from_vec(vec![C2, C0, C2, OMEGA]);
+        ideal.clamp_to(&bound);
+        assert_eq!(ideal, Ideal::from_vec(vec![C2, C0, C1, OMEGA]));
+        assert_eq!(ideal, Ideal::intersection(&Ideal::from_vec(vec![OMEGA, OMEGA, C1, OMEGA]), &bound));
+        // clamping is idempotent
+        ideal.clamp_to(&bound);
+        assert_eq!(ideal, Ideal::from_vec(vec![C2, C0, C1, OMEGA]));
+    }
+
+    #[test]
+    fn image_under() {
+        let edges = Graph::new(3, &[(0, 1), (0, 2), (1, 1), (2, 2)]);
+        let image = Ideal::from_vec(vec![C2, C0, C1]).image_under(&edges, 3);
+        assert_eq!(
+            image,
+            DownSet::from_vecs(&[&[C0, C2, C1], &[C0, C1, C2], &[C0, C0, Coef::Value(3)]])
+        );
+        // rounded up above the maximal value
+        assert_eq!(
+            Ideal::from_vec(vec![C2, C0, C1]).image_under(&edges, 2),
+            DownSet::from_vecs(&[&[C0, C2, C1], &[C0, C1, C2], &[C0, C0, OMEGA]])
+        );
+        assert_eq!(
+            Ideal::from_vec(vec![OMEGA, C1, C0]).image_under(&edges, 3),
+            DownSet::from_vecs(&[&[C0, OMEGA, OMEGA]])
+        );
+        // the sheep in 1 are stuck
+        let stuck = Graph::new(2, &[(0, 1)]);
+        assert!(Ideal::from_vec(vec![C1, C1]).image_under(&stuck, 2).is_empty());
+        assert_eq!(
+            Ideal::from_vec(vec![C1, C0]).image_under(&stuck, 2),
+            DownSet::from_vecs(&[&[C0, C1]])
+        );
+    }
+
+    #[test]
+    fn is_top() {
+        assert!(Ideal::new(3, OMEGA).is_top());
+        assert!(!Ideal::from_vec(vec![OMEGA, C1, OMEGA]).is_top());
+        assert!(!Ideal::new(2, C0).is_top());
+    }
+
+    #[test]
+    fn is_bottom() {
+        assert!(Ideal::new(3, C0).is_bottom());
+        assert!(!Ideal::from_vec(vec![C0, C1, C0]).is_bottom());
+        assert!(!Ideal::new(2, OMEGA).is_bottom());
+    }
+
+    #[test]
+    fn rounding_reports_changes() {
+        let mut ideal = Ideal::from_vec(vec![C0, C1, C2, OMEGA]);
+        assert!(!ideal.round_down(2, 4));
+        assert!(!ideal.round_up_in_place(2));
+        assert_eq!(ideal, Ideal::from_vec(vec![C0, C1, C2, OMEGA]));
+
+        let mut ideal = Ideal::from_vec(vec![C0, C1, C2, OMEGA]);
+        assert!(ideal.round_down(1, 4));
+        assert_eq!(ideal, Ideal::from_vec(vec![C0, C1, C1, OMEGA]));
+
+        let mut ideal = Ideal::from_vec(vec![C0, C1, C2, OMEGA]);
+        assert!(ideal.round_up_in_place(1));
+        assert_eq!(ideal, Ideal::from_vec(vec![C0, C1, OMEGA, OMEGA]));
+    }
+
+    #[test]
+    fn cmp_canonical() {
+        let ideals = [
+            Ideal::from_vec(vec![C0, C0]),
+            Ideal::from_vec(vec![C0, OMEGA]),
+            Ideal::from_vec(vec![C1, C0]),
+            Ideal::from_vec(vec![C1, C2]),
+            Ideal::from_vec(vec![C2, C1]),
+            Ideal::from_vec(vec![OMEGA, C0]),
+            Ideal::from_vec(vec![OMEGA, OMEGA]),
+        ];
+        for x in ideals.iter() {
+            for y in ideals.iter() {
+                let canonical = x.cmp_canonical(y);
+                assert_eq!(canonical, y.cmp_canonical(x).reverse());
+                assert_eq!(canonical == std::cmp::Ordering::Equal, x == y);
+                if x.dominates_strictly(y) {
+                    assert_eq!(canonical, std::cmp::Ordering::Greater);
+                }
+                if let Some(order) = x.partial_cmp(y) {
+                    assert_eq!(order, canonical);
+                }
+            }
+        }
+        assert!(ideals[6].dominates_strictly(&ideals[3]));
+        assert!(!ideals[3].dominates_strictly(&ideals[3]));
+        assert!(!ideals[3].dominates_strictly(&ideals[4]));
+    }
+
+    #[test]
+    fn scale() {
+        let ideal = Ideal::from_vec(vec![C0, C1, C2, OMEGA]);
+        assert_eq!(
+            ideal.scale(2),
+            Ideal::from_vec(vec![C0, C2, Coef::Value(4), OMEGA])
+        );
+        let large = Ideal::from_vec(vec![Coef::Value(200), OMEGA]);
+        assert_eq!(
+            large.scale(2),
+            Ideal::from_vec(vec![Coef::Value(OMEGA_SENTINEL - 1), OMEGA])
+        );
+    }
+
+    #[test]
+    fn join() {
+        let ideal0 = Ideal::from_vec(vec![C0, C1, C2, OMEGA]);
+        let ideal1 = Ideal::from_vec(vec![OMEGA, C2, C1, C0]);
+        let join = Ideal::from_vec(vec![OMEGA, C2, C2, OMEGA]);
+        assert_eq!(join, Ideal::join(&ideal0, &ideal1));
+    }
+
     //from_non_zero_coefs
     #[test]
     fn from_non_zero_coefs() {
         let ideal = Ideal::from_non_zero_coefs(4, &[1, 2], &[1, 3]);
         assert_eq!(ideal, Ideal::from_vec(vec![C0, C1, C0, C2]));
     }
+
+    #[test]
+    fn distance() {
+        let x = Ideal::from_vec(vec![C0, C2, OMEGA]);
+        let y = Ideal::from_vec(vec![C1, C0, OMEGA]);
+        assert_eq!(x.distance(&x), 0);
+        assert_eq!(x.distance(&y), 3);
+        assert_eq!(y.distance(&x), 3);
+        let z = Ideal::from_vec(vec![C1, C0, C2]);
+        assert_eq!(y.distance(&z), OMEGA_DISTANCE);
+    }
+
+    #[allow(clippy::neg_cmp_op_on_partial_ord)]
+    #[test]
+    fn cmp_different_dimensions() {
+        // zip used to truncate the longer ideal, making (1) <= (1, 2)
+        let short = Ideal::from_vec(vec![C1]);
+        let long = Ideal::from_vec(vec![C1, C2]);
+        assert_eq!(short.partial_cmp(&long), None);
+        assert!(!(short <= long));
+        assert!(!(long >= short));
+        assert_eq!(short.partial_cmp(&short), Some(std::cmp::Ordering::Equal));
+    }
 }
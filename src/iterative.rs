@@ -7,10 +7,13 @@ mod logging;
 use regex::Regex;
 use std::fs::File;
 use std::io;
-use std::io::{Read, Write};
-use std::process::{Command, Stdio};
+use std::io::Write;
+use std::process::{self, Command, Stdio};
 
 const PRISM_CMD: &str = "prism";
+const PRISM_PROPERTY: &str = "Pmax=? [ F \"final\" ]";
+// name of the label marking the global target in the prism model
+const TARGET_LABEL: &str = "final";
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -48,6 +51,22 @@ pub struct Args {
         help = "Optional path to the log file. Defaults to stdout if not specified."
     )]
     pub log_output: Option<PathBuf>,
+
+    #[arg(
+        long = "prism-cmd",
+        value_name = "PRISM_CMD",
+        default_value = PRISM_CMD,
+        help = "The prism executable to call."
+    )]
+    pub prism_cmd: String,
+
+    #[arg(
+        long = "prism-property",
+        value_name = "PROPERTY",
+        default_value = PRISM_PROPERTY,
+        help = "The property checked by prism, the target label is \"final\"."
+    )]
+    pub prism_property: String,
 }
 
 pub fn main() {
@@ -58,11 +77,18 @@ pub fn main() {
     logging::setup_logger(args.verbosity, args.log_output);
 
     // parse the input file
-    let mut nfa = nfa::Nfa::load_from_file(
+    let mut nfa = match nfa::Nfa::try_load_from_file_with_dot_init_id(
         &args.filename,
         &args.input_format,
         &nfa::StateOrdering::Alphabetical,
-    );
+        nfa::DOT_INIT_ID,
+    ) {
+        Ok(nfa) => nfa,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
 
     // print the input automaton
     info!("{}", nfa);
@@ -74,7 +100,7 @@ pub fn main() {
         match nfa.add_state("SINK") {
             Ok(sink) => {
                 info!("Added sink state");
-                nfa.complete(Some(sink));
+                nfa.complete(nfa::CompletionMode::Sink(sink));
             }
             Err(e) => {
                 info!("Error adding sink state: {}", e);
@@ -88,23 +114,31 @@ pub fn main() {
     let mut i = 1;
     loop {
         // create prism input string
-        let prism_model = nfa_to_prism(&nfa, i);
+        let prism_model = nfa_to_prism(&nfa, i, TARGET_LABEL);
         debug!("{}", prism_model);
 
         // write prism input to file
         let prism_input_path = args.tmp_dir.join(format!("model-{}.pm", i));
         if let Err(e) = write_string_to_file(&prism_model, &prism_input_path) {
             eprintln!("Error writing to file: {}", e);
-            return;
+            process::exit(1);
         }
         info!("Wrote prism input to file: {}", prism_input_path.display());
 
-        let value = call_prism(&[
-            "-pf",
-            "Pmax=? [ F \"final\" ]",
-            &prism_input_path.to_string_lossy(),
-        ])
-        .unwrap();
+        let value = match call_prism(
+            &args.prism_cmd,
+            &[
+                "-pf",
+                &args.prism_property,
+                &prism_input_path.to_string_lossy(),
+            ],
+        ) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!("Error calling prism: {}", e);
+                process::exit(1);
+            }
+        };
         println!("n={} -> {:.3}", i, value);
 
         if value < 1.0 {
@@ -121,39 +155,51 @@ fn write_string_to_file(content: &str, file_path: &PathBuf) -> io::Result<()> {
     Ok(())
 }
 
-fn call_prism(args: &[&str]) -> Result<f32, ()> {
-    let child = Command::new(PRISM_CMD)
+fn call_prism(prism_cmd: &str, args: &[&str]) -> Result<f32, String> {
+    let child = Command::new(prism_cmd)
         .args(args)
         .stdout(Stdio::piped())
-        .spawn()
-        .expect("Failed to call prism");
-
-    let stdout = child.stdout.expect("Failed to capture stdout");
+        .output()
+        .map_err(|e| format!("failed to run '{}': {}", prism_cmd, e))?;
 
-    // Using stdout to read the output from the child process
-    let mut output = String::new();
-    io::BufReader::new(stdout)
-        .read_to_string(&mut output)
-        .expect("Failed to read from stdout");
+    let output = String::from_utf8_lossy(&child.stdout);
     info!(
         "PRISM OUTPUT\n---------------\n{}\n-----------------",
         output
     );
+    if !child.status.success() {
+        // PRISM reports some errors, e.g. on the model or the property, on stdout
+        let stderr = String::from_utf8_lossy(&child.stderr);
+        let details = if stderr.trim().is_empty() {
+            &output
+        } else {
+            &stderr
+        };
+        return Err(format!(
+            "'{}' failed with {}: {}",
+            prism_cmd,
+            child.status,
+            details.trim()
+        ));
+    }
 
     // Compile the regular expression once
     let re = Regex::new(r"Value in the initial state: (\d+\.\d+)").unwrap();
 
     for line in output.lines() {
-        if let Some(captures) = re.captures(&line) {
+        if let Some(captures) = re.captures(line) {
             if let Some(value) = captures.get(1) {
-                return value.as_str().parse::<f32>().map_err(|_| ());
+                return value
+                    .as_str()
+                    .parse::<f32>()
+                    .map_err(|e| format!("could not parse value '{}': {}", value.as_str(), e));
             }
         }
     }
-    Err(())
+    Err(format!("no value found in the output of '{}'", prism_cmd))
 }
 
-fn nfa_to_prism(nfa: &nfa::Nfa, n: usize) -> String {
+fn nfa_to_prism(nfa: &nfa::Nfa, n: usize, target_label: &str) -> String {
     let mut prism_input = String::new();
     prism_input.push_str("mdp\n\n");
 
@@ -170,17 +216,16 @@ fn nfa_to_prism(nfa: &nfa::Nfa, n: usize) -> String {
     ));
 
     // define transitions
-    for (act, am) in nfa.get_edges().iter() {
+    for (act, am) in nfa.letter_supports_sorted().iter() {
         // for every alphabet letter
         for src in 0..am.dim() {
             // for all states
-            let succs = am.get_successors(src); // get successors
-                                                // prism requires explicit floating point numbers to represent distributions.
-                                                // here we represent a uniform dist among successors.
-            let prob = 1.0 / succs.len() as f64;
-            let update = succs
+            // prism requires explicit floating point numbers to represent distributions.
+            // here we use the weights of the transitions, uniform if none is given.
+            let update = nfa
+                .weighted_successors(src, act)
                 .iter()
-                .map(|trg| format!("{}:(s1'={})", prob, trg))
+                .map(|(trg, prob)| format!("{}:(s1'={})", prob, trg))
                 .collect::<Vec<String>>()
                 .join(" + ");
             prism_input.push_str(&format!("[{act}] s1={} -> {};\n", src, update));
@@ -195,7 +240,7 @@ fn nfa_to_prism(nfa: &nfa::Nfa, n: usize) -> String {
 
     // define a label representing global reachability target:
     // every component is in one of its final states.
-    let mut final_line = String::from("\nlabel \"final\" = ");
+    let mut final_line = format!("\nlabel \"{}\" = ", target_label);
 
     let mut conj = Vec::new();
     for i in 1..=n {
@@ -229,3 +274,49 @@ fn nfa_to_prism(nfa: &nfa::Nfa, n: usize) -> String {
 
     prism_input
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prism_uses_weights() {
+        let mut nfa = nfa::Nfa::from_states(&["q0", "q1", "q2"]);
+        nfa.add_initial("q0");
+        nfa.add_final("q2");
+        nfa.add_weighted_transition("q0", "q1", "a", Some(0.25));
+        nfa.add_weighted_transition("q0", "q2", "a", Some(0.75));
+        nfa.add_transition("q1", "q1", "a");
+        nfa.add_transition("q1", "q2", "a");
+        nfa.add_transition("q2", "q2", "a");
+        let prism = nfa_to_prism(&nfa, 1, TARGET_LABEL);
+        assert!(prism.contains("[a] s1=0 -> 0.25:(s1'=1) + 0.75:(s1'=2);"));
+        assert!(prism.contains("[a] s1=1 -> 0.5:(s1'=1) + 0.5:(s1'=2);"));
+        assert!(prism.contains("[a] s1=2 -> 1:(s1'=2);"));
+    }
+
+    #[test]
+    fn prism_custom_label() {
+        let mut nfa = nfa::Nfa::from_states(&["q0", "q1"]);
+        nfa.add_initial("q0");
+        nfa.add_final("q1");
+        nfa.add_transition("q0", "q1", "a");
+        nfa.add_transition("q1", "q1", "a");
+        let prism = nfa_to_prism(&nfa, 2, "target");
+        assert!(prism.contains("label \"target\" = ( s1=1 ) & ( s2=1 );"));
+        assert!(!prism.contains("\"final\""));
+    }
+
+    #[test]
+    fn call_prism_reports_missing_command() {
+        let result = call_prism("/nonexistent/prism", &["-version"]);
+        assert!(result.unwrap_err().contains("/nonexistent/prism"));
+    }
+
+    #[test]
+    fn call_prism_reports_failure() {
+        let script = "echo 'Value in the initial state: 1.0'; echo oops >&2; exit 3";
+        let error = call_prism("sh", &["-c", script]).unwrap_err();
+        assert!(error.contains("oops"), "{}", error);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod coef;
 pub mod downset;
+pub mod examples;
 pub mod flow;
 pub mod graph;
 pub mod ideal;
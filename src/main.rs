@@ -1,36 +1,135 @@
 use clap::Parser;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
 use log::info;
 
+use shepherd::examples;
 use shepherd::solver;
 use shepherd::nfa;
+use shepherd::solution::Solution;
 
 mod cli;
 mod logging;
+mod pdf;
 
 pub fn main() {
     // parse CLI arguments
     let args = cli::Args::parse();
 
     // set up logging
-    logging::setup_logger(args.verbosity, args.log_output);
+    logging::setup_logger(args.verbosity, args.log_output.clone());
+
+    // the output is canonical anyway, the flag is kept for scripts passing it
+    if args.deterministic {
+        info!("--deterministic has no effect, the output is always deterministic");
+    }
+
+    // smoke test on the bundled examples
+    if args.run_examples {
+        process::exit(if run_examples() { 0 } else { 1 });
+    }
+
+    // solve several automata at once, one summary line each, and possibly one file each
+    if args.filenames.len() > 1 || args.output_dir.is_some() {
+        solve_batch(&args);
+        return;
+    }
+    let filename = args.filenames[0].clone();
 
     // parse the input file
-    let nfa = nfa::Nfa::load_from_file(&args.filename, &args.input_format, &args.state_ordering);
+    let (nfa, parsing_time) = load_nfa(&args, &filename);
 
-    // print the input automaton
-    info!("{}", nfa);
+    // only convert the automaton if requested
+    if args.parse_only {
+        let output = match args.output_format {
+            cli::OutputFormat::GraphMl => nfa.to_graphml(),
+            cli::OutputFormat::Plain
+            | cli::OutputFormat::Tex
+            | cli::OutputFormat::Pdf
+            | cli::OutputFormat::Csv
+            | cli::OutputFormat::Markdown
+            | cli::OutputFormat::Summary => format!("{}\n", nfa),
+        };
+        write!(output_writer(args.output_path), "{}", output).expect("Couldn’t write");
+        return;
+    }
 
     // compute the solution
-    let solution = solver::solve(&nfa, &args.solver_output);
+    let solver_output = if args.quiet {
+        solver::SolverOutput::Quiet
+    } else {
+        args.solver_output.clone()
+    };
+    let options = solver::SolverOptions {
+        max_flows: args.max_flows,
+        source_semantics: args.source_semantics,
+        max_value: args.max_value,
+        initial_config: args.initial_config.clone(),
+        serial: args.no_parallel,
+        warn_on_nondeterminism: args.warn_on_nondeterminism,
+    };
+    let mut solution = match solver::try_solve(&nfa, &solver_output, &options) {
+        Ok(solution) => solution,
+        Err(why) => {
+            eprintln!("Cannot solve {}: {}", filename, why);
+            process::exit(2);
+        }
+    };
+    solution.statistics.parsing = parsing_time;
+
+    // keep only what the strategy needs to win from the initial states
+    if args.minimize_strategy && solution.is_controllable {
+        solver::minimize_winning_strategy(&nfa, &mut solution.winning_strategy, &options);
+    }
+
+    // let the controller choose among fewer letters if requested
+    if args.determinize_controller && solution.is_controllable {
+        solver::determinize_winning_controller(&nfa, &mut solution.winning_strategy, &options);
+    }
+
+    // dump the flow semigroup if requested
+    if let Some(path) = &args.dump_semigroup {
+        if let Err(why) = solution.semigroup.dump(path) {
+            eprintln!("Cannot write the semigroup to {}: {}", path.display(), why);
+            process::exit(2);
+        }
+    }
+
+    // print where the time went if requested
+    if args.profile {
+        eprintln!("\nProfile\n{}", solution.statistics);
+    }
+
+    // in quiet mode, the exit code is the only output
+    if let solver::SolverOutput::Quiet = solver_output {
+        process::exit(if solution.is_controllable { 0 } else { 1 });
+    }
+
+    // in summary mode, a single line is the only output
+    if args.output_format == cli::OutputFormat::Summary {
+        println!("{}", solution.summary_line(&filename));
+        return;
+    }
+
+    // report whether the controller has choices if requested
+    if args.check_determinism {
+        if solution.winning_strategy.is_deterministic_controller() {
+            println!("\nThe controller is deterministic");
+        } else {
+            println!("\nThe controller is not deterministic");
+        }
+    }
 
     // print the solution in any case.
     // This now only prints the status: controllable or not.
-    match args.solver_output {
+    match solver_output {
         solver::SolverOutput::Strategy => println!("\nMaximal winning strategy;\n{}", solution),
-        solver::SolverOutput::YesNo => {
+        solver::SolverOutput::YesNo | solver::SolverOutput::Quiet => {
             println!("\nSolution\n{}", solution);
             if solution.is_controllable {
                 println!(
@@ -39,53 +138,252 @@ pub fn main() {
                 );
             }
         }
+        // the maximal winning strategy is written below, in the output format
+        solver::SolverOutput::Both => println!("\nSolution\n{}", solution),
     }
 
     // only if the answer was positive, format the winning strategy
-    let output_strategy = match args.solver_output {
-        solver::SolverOutput::Strategy => true,
-        solver::SolverOutput::YesNo => solution.is_controllable,
-    };
-    if output_strategy {
-        // create a writer were we later print the output.
-        // This is either a file or simply stdout.
-        let mut out_writer = match args.output_path {
-            Some(path) => {
-                // Open a file in write-only mode, returns `io::Result<File>`
-                let file = match File::create(&path) {
-                    Err(why) => panic!("couldn't create {}: {}", path.display(), why),
-                    Ok(file) => file,
-                };
-                Box::new(file) as Box<dyn Write>
-            }
-            None => Box::new(io::stdout()) as Box<dyn Write>,
+    let output_strategy = outputs_strategy(&solver_output, &solution);
+    if output_strategy && args.output_format == cli::OutputFormat::Pdf {
+        let Some(output_path) = &args.output_path else {
+            eprintln!("--to pdf needs an output file, given with --output");
+            process::exit(2);
         };
+        let is_tikz = args.input_format == nfa::InputFormat::Tikz;
+        //the report is compiled elsewhere, the tikz input must be found from there
+        let tikz_path = std::fs::canonicalize(&filename)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or(filename.clone());
+        let latex = solution.as_latex(if is_tikz { Some(&tikz_path) } else { None });
+        if let Err(why) = pdf::latex_to_pdf(&args.tex_cmd, &latex, output_path) {
+            eprintln!("Cannot produce the PDF report: {}", why);
+            process::exit(2);
+        }
+    } else if output_strategy {
+        // create a writer were we later print the output.
+        let mut out_writer = output_writer(args.output_path.clone());
 
-        // prepare output string
-        let output = match args.output_format {
-            cli::OutputFormat::Tex => {
-                let is_tikz = args.input_format == nfa::InputFormat::Tikz;
-                let latex_content =
-                    solution.as_latex(if is_tikz { Some(&args.filename) } else { None });
-                latex_content.to_string()
-            }
-            cli::OutputFormat::Plain => {
-                format!(
-                    "States: {}\n {}",
-                    nfa.states_str(),
-                    solution.winning_strategy
-                )
+        // Write the winning strategy to the output
+        let output = format_solution(&args, &solution, &filename);
+        write!(out_writer, "{}", output).expect("Couldn’t write");
+    }
+}
+
+/// Whether the winning strategy is written: always if the maximal one was asked for,
+/// otherwise only if the answer was positive.
+fn outputs_strategy(solver_output: &solver::SolverOutput, solution: &Solution) -> bool {
+    match solver_output {
+        solver::SolverOutput::Strategy | solver::SolverOutput::Both => true,
+        solver::SolverOutput::YesNo | solver::SolverOutput::Quiet => solution.is_controllable,
+    }
+}
+
+/// The solution in the output format, which is neither pdf nor summary.
+fn format_solution(args: &cli::Args, solution: &Solution, filename: &str) -> String {
+    let nfa = &solution.nfa;
+    match args.output_format {
+        cli::OutputFormat::Tex => {
+            let is_tikz = args.input_format == nfa::InputFormat::Tikz;
+            let latex_content = solution.as_latex(if is_tikz { Some(filename) } else { None });
+            latex_content.to_string()
+        }
+        cli::OutputFormat::Plain => {
+            format!(
+                "{}\n",
+                solution.winning_strategy.to_string_with_states(&nfa.states())
+            )
+        }
+        cli::OutputFormat::Csv => {
+            format!(
+                "{}\n",
+                solution.winning_strategy.as_csv_with_states(&nfa.states())
+            )
+        }
+        cli::OutputFormat::Markdown => solution.as_markdown(),
+        cli::OutputFormat::GraphMl => nfa.to_graphml(),
+        cli::OutputFormat::Pdf => unreachable!("the PDF report is compiled separately"),
+        cli::OutputFormat::Summary => unreachable!("the summary is printed separately"),
+    }
+}
+
+/// Parses the input file and applies the command-line transformations, returns the parsing time.
+fn load_nfa(args: &cli::Args, filename: &str) -> (nfa::Nfa, Duration) {
+    let parsing_start = Instant::now();
+    let mut nfa = match nfa::Nfa::try_load_from_file_with_dot_init_id(
+        filename,
+        &args.input_format,
+        &args.state_ordering,
+        &args.dot_init_id,
+    ) {
+        Ok(nfa) => nfa,
+        Err(why) => {
+            eprintln!("{}", why);
+            process::exit(2);
+        }
+    };
+    let parsing_time = parsing_start.elapsed();
+
+    // mark accepting states by name if requested
+    if let Some(pattern) = &args.accepting_regex {
+        match nfa.mark_accepting_by_regex(pattern) {
+            Ok(count) => info!("{} states match the accepting regex", count),
+            Err(why) => {
+                eprintln!("Invalid accepting regex '{}': {}", pattern, why);
+                process::exit(2);
             }
-            cli::OutputFormat::Csv => {
-                format!(
-                    "Σ, {}\n{}\n",
-                    nfa.states().join(","),
-                    solution.winning_strategy.as_csv()
-                )
+        }
+    }
+
+    // complete the automaton with a sink state if requested
+    if args.complete {
+        nfa.complete_with_sink();
+    } else if args.complete_with_self_loops {
+        nfa.complete(nfa::CompletionMode::SelfLoop);
+    }
+
+    // print the input automaton
+    info!("{}", nfa);
+    (nfa, parsing_time)
+}
+
+/// Solves all input files together and prints a summary line for each, in the given order.
+/// The options about a single solution are rejected.
+fn solve_batch(args: &cli::Args) {
+    let single_automaton_flags = [
+        ("--initial-config", args.initial_config.is_some()),
+        ("--parse-only", args.parse_only),
+        ("--minimize-strategy", args.minimize_strategy),
+        ("--determinize-controller", args.determinize_controller),
+        ("--check-determinism-of-controller", args.check_determinism),
+        ("--dump-semigroup", args.dump_semigroup.is_some()),
+        ("--profile", args.profile),
+    ];
+    if let Some((flag, _)) = single_automaton_flags.iter().find(|(_, set)| *set) {
+        eprintln!("{} only applies to a single automaton, without --output-dir", flag);
+        process::exit(2);
+    }
+    let (nfas, parsing_times): (Vec<_>, Vec<_>) = args
+        .filenames
+        .iter()
+        .map(|filename| load_nfa(args, filename))
+        .unzip();
+    let options = solver::SolverOptions {
+        max_flows: args.max_flows,
+        source_semantics: args.source_semantics,
+        max_value: args.max_value,
+        initial_config: None,
+        serial: args.no_parallel,
+        warn_on_nondeterminism: args.warn_on_nondeterminism,
+    };
+    //an exit code cannot answer for several automata
+    let solver_output = match &args.solver_output {
+        solver::SolverOutput::Quiet => solver::SolverOutput::YesNo,
+        output => output.clone(),
+    };
+    if let Some(dir) = &args.output_dir {
+        //files with the same stem would overwrite each other
+        let mut written_by: HashMap<String, &String> = HashMap::new();
+        for filename in args.filenames.iter() {
+            if let Some(other) = written_by.insert(file_stem(filename), filename) {
+                eprintln!(
+                    "{} and {} would both be written to {}",
+                    other,
+                    filename,
+                    output_path_in(args, filename, dir).display()
+                );
+                process::exit(2);
             }
-        };
+        }
+        if let Err(why) = std::fs::create_dir_all(dir) {
+            eprintln!("Cannot create {}: {}", dir.display(), why);
+            process::exit(2);
+        }
+    }
+    let solutions = solver::solve_all_with_options(&nfas, &solver_output, &options);
+    for ((filename, mut solution), parsing_time) in
+        args.filenames.iter().zip(solutions).zip(parsing_times)
+    {
+        solution.statistics.parsing = parsing_time;
+        println!("{}", solution.summary_line(filename));
+        //the summary line is written in any case, as on stdout
+        let written = args.output_format == cli::OutputFormat::Summary
+            || outputs_strategy(&solver_output, &solution);
+        if let (Some(dir), true) = (&args.output_dir, written) {
+            write_to_dir(args, &solution, filename, dir);
+        }
+    }
+}
 
-        // Write the winning strategy to the output
-        write!(out_writer, "{}", output).expect("Couldn’t write");
+fn file_stem(filename: &str) -> String {
+    Path::new(filename)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| filename.to_string())
+}
+
+/// `dir/<stem>.<ext>`, the extension depending on the output format.
+fn output_path_in(args: &cli::Args, filename: &str, dir: &Path) -> PathBuf {
+    dir.join(format!("{}.{}", file_stem(filename), args.output_format.extension()))
+}
+
+/// Writes the solution to `dir/<stem>.<ext>`, see `output_path_in`.
+fn write_to_dir(args: &cli::Args, solution: &Solution, filename: &str, dir: &Path) {
+    let path = output_path_in(args, filename, dir);
+    let written = match args.output_format {
+        cli::OutputFormat::Pdf => {
+            let is_tikz = args.input_format == nfa::InputFormat::Tikz;
+            let tikz_path = std::fs::canonicalize(filename)
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or(filename.to_string());
+            let latex = solution.as_latex(if is_tikz { Some(&tikz_path) } else { None });
+            pdf::latex_to_pdf(&args.tex_cmd, &latex, &path)
+        }
+        cli::OutputFormat::Summary => {
+            std::fs::write(&path, format!("{}\n", solution.summary_line(filename)))
+                .map_err(|why| why.to_string())
+        }
+        _ => std::fs::write(&path, format_solution(args, solution, filename))
+            .map_err(|why| why.to_string()),
+    };
+    if let Err(why) = written {
+        eprintln!("Cannot write {}: {}", path.display(), why);
+        process::exit(2);
+    }
+}
+
+/// Where the output goes: either a file or simply stdout.
+fn output_writer(output_path: Option<PathBuf>) -> Box<dyn Write> {
+    match output_path {
+        Some(path) => {
+            // Open a file in write-only mode, returns `io::Result<File>`
+            let file = match File::create(&path) {
+                Err(why) => panic!("couldn't create {}: {}", path.display(), why),
+                Ok(file) => file,
+            };
+            Box::new(file) as Box<dyn Write>
+        }
+        None => Box::new(io::stdout()) as Box<dyn Write>,
+    }
+}
+
+/// Solves every bundled example and prints expected vs. actual answers.
+/// Returns true if all answers match.
+fn run_examples() -> bool {
+    let answer = |controllable: bool| if controllable { "YES" } else { "NO" };
+    let mut all_match = true;
+    println!("{:<32}{:<10}{:<10}", "example", "expected", "actual");
+    for (name, content, expected) in examples::EXAMPLES {
+        let nfa = nfa::Nfa::from_tikz(content);
+        let actual = solver::solve(&nfa, &solver::SolverOutput::YesNo).is_controllable;
+        let status = if actual == *expected { "" } else { "MISMATCH" };
+        all_match &= actual == *expected;
+        println!(
+            "{:<32}{:<10}{:<10}{}",
+            name,
+            answer(*expected),
+            answer(actual),
+            status
+        );
     }
+    all_match
 }
@@ -9,6 +9,7 @@ where
 {
     cache: HashMap<K, V>,
     compute: F,
+    hits: u64,
 }
 
 impl<K, V, F> Memoizer<K, V, F>
@@ -21,15 +22,22 @@ where
         Self {
             cache: HashMap::new(),
             compute,
+            hits: 0,
         }
     }
 
     pub fn get(&mut self, key: K) -> V {
         if let Some(value) = self.cache.get(&key) {
+            self.hits += 1;
             return value.clone();
         }
         let value = (self.compute)(&key);
         self.cache.insert(key.clone(), value.clone());
         value
     }
+
+    /// Number of lookups served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
 }
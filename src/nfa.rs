@@ -1,15 +1,22 @@
 /*
 authors @GBathie + @Numero7
  */
+use crate::coef::{Coef, C0};
 use crate::graph::Graph;
+use crate::ideal::Ideal;
 use clap::ValueEnum;
 use dot_parser::*;
+use itertools::Itertools;
+use log::{info, warn};
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::fs::File;
 use std::io::{self, Read};
 
+/// Label of the sink state added by `Nfa::complete_with_sink`.
+pub const SINK_LABEL: &str = "⊥";
+
 pub type State = usize;
 pub type Letter = String;
 
@@ -18,20 +25,69 @@ pub struct Transition {
     pub from: State,
     pub label: Letter,
     pub to: State,
+    /// optional probability weight, parsed from labels of the form `a:0.3`
+    pub weight: Option<f64>,
 }
 
+/// Id of the artificial DOT node whose edges point to the initial states.
+pub const DOT_INIT_ID: &str = "init";
+
 #[derive(Debug, Clone)]
 pub struct Nfa {
     states: Vec<String>,
     initial: HashSet<State>,
     accepting: HashSet<State>,
     transitions: Vec<Transition>,
+    /// number of sheep per state name at the start, if not omega on every initial state
+    initial_config: Option<HashMap<String, Coef>>,
 }
 
 #[derive(Debug, Clone, ValueEnum, PartialEq, Eq)]
 pub enum InputFormat {
     Dot,
     Tikz,
+    Text,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+/// Errors of `Nfa::from_json`, `Nfa::try_from_tikz` and `Nfa::try_from_dot_with_init_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NfaParseError {
+    /// the input does not describe an automaton
+    Syntax(String),
+    /// a state is used but not declared in the list of states
+    UnknownState(String),
+}
+
+impl fmt::Display for NfaParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NfaParseError::Syntax(e) => write!(f, "malformed automaton: {}", e),
+            NfaParseError::UnknownState(state) => write!(f, "undeclared state '{}'", state),
+        }
+    }
+}
+
+impl std::error::Error for NfaParseError {}
+
+//the shape of an automaton in JSON, transitions are (from, letter, to) triples
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonNfa {
+    states: Vec<String>,
+    initial: Vec<String>,
+    accepting: Vec<String>,
+    transitions: Vec<(String, String, String)>,
+}
+
+/// Where `Nfa::complete` sends the missing transitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionMode {
+    /// to the given state, usually a non-accepting sink
+    Sink(State),
+    /// back to their source: the letter has no effect there
+    SelfLoop,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -59,6 +115,7 @@ impl Nfa {
                 .map(|t| t.label.clone())
                 .collect::<Vec<_>>();
             state_actions.sort();
+            state_actions.dedup();
             if state_actions != letters {
                 return false;
             }
@@ -66,13 +123,101 @@ impl Nfa {
         true
     }
 
-    /// completes the nfa by adding self-loops for every letter in the alphabet
-    ///
-    /// This takes an optional state to be used as target for the new transitions.
-    /// If this is None, new transitions will be self-loops.
+    /// checks if the nfa is deterministic:
+    /// at most one initial state and at most one successor by every letter from every state.
+    pub fn is_deterministic(&self) -> bool {
+        self.initial.len() <= 1
+            && self
+                .transitions
+                .iter()
+                .map(|t| (t.from, &t.label))
+                .all(|(q, a)| self.successors_on(q, a).len() <= 1)
+    }
+
+    /// The pairs of a state and a letter with several successors, sorted.
+    pub fn nondeterministic_pairs(&self) -> Vec<(State, Letter)> {
+        self.transitions
+            .iter()
+            .map(|t| (t.from, t.label.clone()))
+            .sorted()
+            .unique()
+            .filter(|(q, a)| self.successors_on(*q, a).len() > 1)
+            .collect()
+    }
+
+    /// The subset construction: the initial state is the set of initial states,
+    /// and the successor of a set by a letter is the set of successors of its elements,
+    /// when non-empty. A set is accepting iff it contains an accepting state,
+    /// and is named after its elements, e.g. `{p,q}`, with a suffix `#2`, `#3`...
+    /// if states with commas in their names make two sets look alike.
+    /// Fails if there are more than `max_states` reachable sets.
+    pub fn determinize(&self, max_states: usize) -> Result<Nfa, String> {
+        let mut dfa = Nfa::from_states(&[]);
+        if self.initial.is_empty() {
+            return Ok(dfa);
+        }
+        let letters = self.get_alphabet();
+        let initial = self.initial.iter().copied().sorted().collect_vec();
+        let mut indices: HashMap<Vec<State>, State> = HashMap::from([(initial.clone(), 0)]);
+        let mut subsets = vec![initial];
+        let mut to_process = 0;
+        let mut edges = Vec::new();
+        while to_process < subsets.len() {
+            let subset = subsets[to_process].clone();
+            for &letter in &letters {
+                let successors = subset
+                    .iter()
+                    .flat_map(|&q| self.successors_on(q, letter))
+                    .sorted()
+                    .unique()
+                    .collect_vec();
+                if successors.is_empty() {
+                    continue;
+                }
+                let target = match indices.get(&successors) {
+                    Some(&target) => target,
+                    None if subsets.len() == max_states => {
+                        return Err(format!(
+                            "the subset construction exceeds {} states",
+                            max_states
+                        ));
+                    }
+                    None => {
+                        indices.insert(successors.clone(), subsets.len());
+                        subsets.push(successors);
+                        subsets.len() - 1
+                    }
+                };
+                edges.push((to_process, target, letter));
+            }
+            to_process += 1;
+        }
+        //the states of the automaton are numbered as the subsets
+        let mut taken: HashSet<String> = HashSet::new();
+        for (index, subset) in subsets.iter().enumerate() {
+            let base = format!("{{{}}}", subset.iter().map(|&q| &self.states[q]).join(","));
+            let name = (1..)
+                .map(|k| if k == 1 { base.clone() } else { format!("{}#{}", base, k) })
+                .find(|name| !taken.contains(name))
+                .unwrap();
+            taken.insert(name.clone());
+            dfa.states.push(name);
+            if subset.iter().any(|q| self.accepting.contains(q)) {
+                dfa.accepting.insert(index);
+            }
+        }
+        dfa.initial.insert(0);
+        for (from, to, letter) in edges {
+            dfa.add_transition_by_index2(from, to, letter);
+        }
+        Ok(dfa)
+    }
+
+    /// completes the nfa by adding a transition for every missing (state, letter) pair,
+    /// either to the given sink state or as a self-loop, depending on the mode.
     ///
     /// This is really horrible because we have to recompute the alphabet lots
-    pub fn complete(&mut self, sink_state: Option<State>) {
+    pub fn complete(&mut self, mode: CompletionMode) {
         // get the alphabet
         let mut letters = self
             .get_alphabet()
@@ -93,11 +238,11 @@ impl Nfa {
             // for every alphabent letter add new transitions
             for letter in &letters {
                 if !state_actions.contains(letter) {
-                    match sink_state {
-                        Some(sink) => {
+                    match mode {
+                        CompletionMode::Sink(sink) => {
                             self.add_transition_by_index2(state, sink, letter);
                         }
-                        None => {
+                        CompletionMode::SelfLoop => {
                             // add a self-loop
                             self.add_transition_by_index2(state, state, letter);
                         }
@@ -107,9 +252,33 @@ impl Nfa {
         }
     }
 
-    /// getter for the states attribute
-    pub fn states(&self) -> &Vec<String> {
-        &self.states
+    /// completes the nfa by sending every missing transition
+    /// to a fresh non-accepting sink state.
+    ///
+    /// Returns the index of the sink, or None if the nfa was already complete
+    /// and no sink was added.
+    pub fn complete_with_sink(&mut self) -> Option<State> {
+        if self.is_complete() {
+            return None;
+        }
+        let mut label = String::from(SINK_LABEL);
+        while self.states.contains(&label) {
+            label.push('\'');
+        }
+        let sink = self.add_state(&label).unwrap();
+        self.complete(CompletionMode::Sink(sink));
+        Some(sink)
+    }
+
+    /// names of the states, in index order
+    pub fn states(&self) -> Vec<&str> {
+        self.states.iter().map(|s| s.as_str()).collect()
+    }
+
+    /// name of the state with index i
+    pub fn state_name(&self, i: State) -> &str {
+        self.check_state(i);
+        &self.states[i]
     }
 
     #[allow(dead_code)]
@@ -119,6 +288,7 @@ impl Nfa {
             initial: HashSet::new(),
             accepting: HashSet::new(),
             transitions: vec![],
+            initial_config: None,
         }
     }
 
@@ -129,12 +299,26 @@ impl Nfa {
             initial: HashSet::new(),
             accepting: HashSet::new(),
             transitions: vec![],
+            initial_config: None,
         }
     }
 
     pub fn from_dot(input: &str) -> Self {
+        Self::from_dot_with_init_id(input, DOT_INIT_ID)
+    }
+
+    /// Same as `from_dot`, with `init_id` as the id of the artificial node
+    /// whose edges point to the initial states, e.g. `__start0`.
+    /// States are sorted by node id, so that the result does not depend on the parser's hashing.
+    /// Panics on invalid weights, see `try_from_dot_with_init_id`.
+    pub fn from_dot_with_init_id(input: &str, init_id: &str) -> Self {
+        Self::try_from_dot_with_init_id(input, init_id).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Same as `from_dot_with_init_id`, fails on invalid weights in the labels.
+    pub fn try_from_dot_with_init_id(input: &str, init_id: &str) -> Result<Self, NfaParseError> {
         // intermediate boxes to hold values
-        let mut states: Vec<String> = Vec::new(); //preserves appearance order in file
+        let mut states: Vec<String> = Vec::new(); //sorted by node id, the parser forgets the file order
         let mut names: HashMap<String, String> = HashMap::new();
         let mut initials: HashSet<String> = HashSet::new();
         let mut finals: HashSet<String> = HashSet::new();
@@ -144,13 +328,15 @@ impl Nfa {
         let graph = canonical::Graph::from(ast::Graph::try_from(input).unwrap());
 
         // extract nodes with labels:
-        // - ignore state with label "init"
-        // - interpret nodes with attribute "shape:doublecircle" as accepting states
-        for (id, node) in graph.nodes.set {
+        // - ignore the artificial node init_id
+        // - interpret nodes with attribute "shape:doublecircle" or "peripheries" >= 2 as accepting states
+        // - interpret nodes with attribute "root:true" or "style:bold" as initial states
+        let nodes = graph.nodes.set.into_iter().sorted_by(|(x, _), (y, _)| x.cmp(y));
+        for (id, node) in nodes {
             //println!("{:#?}", node);  // help me debug
 
-            // skip over artificial "init" state
-            if id.eq("init") {
+            // skip over artificial init state
+            if id.eq(init_id) {
                 continue;
             }
 
@@ -165,20 +351,23 @@ impl Nfa {
                     //println!("  Label: {}", l);
                     names.insert(node.id.clone(), l.to_string());
                 }
-                if k.eq("shape") && v.eq("doublecircle") {
+                if Self::is_dot_accepting_attribute(k, v) {
                     //println!("state {} is accepting", node.id);
                     finals.insert(node.id.clone());
                 }
+                if Self::is_dot_initial_attribute(k, v) {
+                    initials.insert(node.id.clone());
+                }
             }
         }
 
         // interpret edges with labels
-        // also define set of initial states as those where "init" has an edge into.
+        // also define set of initial states as those where init_id has an edge into.
         for edge in graph.edges.set {
             //println!("{:#?}", edge);
 
             // if an edge from init to X exists then X interpreted as initial state.
-            if edge.from.eq("init") {
+            if edge.from.eq(init_id) {
                 //println!("{:#?}", edge);
                 initials.insert(edge.to.clone());
             }
@@ -199,6 +388,7 @@ impl Nfa {
             initial: HashSet::new(),
             accepting: HashSet::new(),
             transitions: vec![],
+            initial_config: None,
         };
         for state in initials {
             nfa.add_initial(&names[&state]);
@@ -207,19 +397,30 @@ impl Nfa {
             nfa.add_final(&names[&state]);
         }
         for (from, label, to) in transitions {
-            nfa.add_transition(&names[&from], &names[&to], &label);
+            let (label, weight) =
+                Self::parse_weighted_label(&label).map_err(NfaParseError::Syntax)?;
+            nfa.add_weighted_transition(&names[&from], &names[&to], label, weight);
         }
-        nfa
+        nfa.check_weights().map_err(NfaParseError::Syntax)?;
+        Ok(nfa)
     }
 
+    /// Panics on invalid weights, see `try_from_tikz`.
     pub fn from_tikz(input: &str) -> Self {
-        let state_re = Regex::new(
-            r"\\node\[(?P<attrs>[^\]]*)\]\s*at\s*\([^)]+\)\s*\((?P<id>\w+)\)\s*\{\$(?P<name>[^$]+)\$\}",
-        )
-        .unwrap();
-        let edge_re =
-            Regex::new(r"\((?P<from>\w+)\)\s*edge.*?\{\$(?P<label>[^$]+)\$\}\s*\((?P<to>\w+)\)")
-                .unwrap();
+        Self::try_from_tikz(input).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Same as `from_tikz`, fails on invalid weights in the labels.
+    pub fn try_from_tikz(input: &str) -> Result<Self, NfaParseError> {
+        let (state_re, edge_re) = Self::tikz_regexes();
+
+        for (line_number, line) in Self::malformed_tikz_lines(input, &state_re, &edge_re) {
+            warn!(
+                "line {}: ignoring malformed tikz declaration '{}'",
+                line_number,
+                line.trim()
+            );
+        }
 
         let mut states: Vec<String> = Vec::new(); //preserves appearance order in file
         let mut names: HashMap<String, String> = HashMap::new();
@@ -260,6 +461,7 @@ impl Nfa {
             initial: HashSet::new(),
             accepting: HashSet::new(),
             transitions: vec![],
+            initial_config: None,
         };
         for state in initials {
             nfa.add_initial(&names[&state]);
@@ -268,9 +470,205 @@ impl Nfa {
             nfa.add_final(&names[&state]);
         }
         for (from, label, to) in transitions {
-            nfa.add_transition(&names[&from], &names[&to], &label);
+            let (label, weight) =
+                Self::parse_weighted_label(&label).map_err(NfaParseError::Syntax)?;
+            nfa.add_weighted_transition(&names[&from], &names[&to], label, weight);
+        }
+        nfa.check_weights().map_err(NfaParseError::Syntax)?;
+        Ok(nfa)
+    }
+
+    //DOT node attributes marking accepting states
+    fn is_dot_accepting_attribute(key: &str, value: &str) -> bool {
+        let value = value.trim_matches('"');
+        match key {
+            "shape" => value == "doublecircle",
+            "peripheries" => value.parse::<u32>().is_ok_and(|n| n >= 2),
+            _ => false,
+        }
+    }
+
+    //DOT node attributes marking initial states, besides an edge from the init node
+    fn is_dot_initial_attribute(key: &str, value: &str) -> bool {
+        let value = value.trim_matches('"');
+        match key {
+            "root" => value == "true",
+            "style" => value.split(',').any(|style| style.trim() == "bold"),
+            _ => false,
+        }
+    }
+
+    /// Parse a line-based transition list.
+    /// Each line is either a transition `p a q` (also written `p -a-> q`),
+    /// `initial: p ...` or `accepting: q ...`.
+    /// An optional `init-config: 2, 0, omega` gives the initial configuration,
+    /// one coefficient per state in order of first mention.
+    /// States are registered on first mention, `#` starts a comment.
+    /// Fails on a malformed line, with its number.
+    pub fn from_text(input: &str) -> Result<Self, String> {
+        let mut nfa = Nfa::from_size(0);
+        let mut initial_config = None;
+        for (line_number, line) in input.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let line_number = line_number + 1;
+            if let Some((kind, states)) = line.split_once(':') {
+                let kind = kind.trim();
+                if kind == "init-config" {
+                    initial_config = Some((line_number, states));
+                    continue;
+                }
+                if kind == "initial" || kind == "accepting" {
+                    for state in states.split_whitespace() {
+                        nfa.register_state(state);
+                        match kind {
+                            "initial" => nfa.add_initial(state),
+                            _ => nfa.add_final(state),
+                        }
+                    }
+                    continue;
+                }
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (from, label, to) = match words[..] {
+                [from, label, to] => (from, label, to),
+                _ => return Err(format!("line {}: malformed transition '{}'", line_number, line)),
+            };
+            let label = match label.strip_prefix('-') {
+                Some(arrow) => match arrow.strip_suffix("->") {
+                    Some(label) if !label.is_empty() => label,
+                    _ => {
+                        return Err(format!("line {}: malformed arrow '{}'", line_number, words[1]))
+                    }
+                },
+                None => label,
+            };
+            nfa.register_state(from);
+            nfa.register_state(to);
+            let (label, weight) = Self::parse_weighted_label(label)
+                .map_err(|e| format!("line {}: {}", line_number, e))?;
+            nfa.add_weighted_transition(from, to, label, weight);
+        }
+        nfa.check_weights()?;
+        if let Some((line_number, config)) = initial_config {
+            match config.parse::<Ideal>() {
+                Ok(config) if config.dimension() == nfa.nb_states() => {
+                    nfa.set_initial_config(&config)
+                }
+                Ok(config) => {
+                    return Err(format!(
+                        "line {}: initial configuration of size {} for {} states",
+                        line_number,
+                        config.dimension(),
+                        nfa.nb_states()
+                    ))
+                }
+                Err(e) => return Err(format!("line {}: {}", line_number, e)),
+            }
         }
-        nfa
+        Ok(nfa)
+    }
+
+    /// Parse an automaton given as
+    /// `{ "states": [...], "initial": [...], "accepting": [...], "transitions": [["p", "a", "q"], ...] }`.
+    /// Labels of the form `a:0.3` carry a weight, as in the other formats.
+    #[cfg(feature = "serde")]
+    pub fn from_json(input: &str) -> Result<Self, NfaParseError> {
+        let json: JsonNfa =
+            serde_json::from_str(input).map_err(|e| NfaParseError::Syntax(e.to_string()))?;
+        let states: Vec<&str> = json.states.iter().map(|s| s.as_str()).collect();
+        let mut nfa = Nfa::from_states(&states);
+        let check = |state: &String| match states.contains(&state.as_str()) {
+            true => Ok(()),
+            false => Err(NfaParseError::UnknownState(state.clone())),
+        };
+        for state in json.initial.iter() {
+            check(state)?;
+            nfa.add_initial(state);
+        }
+        for state in json.accepting.iter() {
+            check(state)?;
+            nfa.add_final(state);
+        }
+        for (from, label, to) in json.transitions.iter() {
+            check(from)?;
+            check(to)?;
+            let (label, weight) =
+                Self::parse_weighted_label(label).map_err(NfaParseError::Syntax)?;
+            nfa.add_weighted_transition(from, to, label, weight);
+        }
+        nfa.check_weights().map_err(NfaParseError::Syntax)?;
+        Ok(nfa)
+    }
+
+    /// JSON description of the automaton, read back by `from_json`.
+    /// The initial configuration is not exported.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        let names = |states: &HashSet<State>| {
+            states
+                .iter()
+                .sorted()
+                .map(|&q| self.states[q].clone())
+                .collect()
+        };
+        let json = JsonNfa {
+            states: self.states.clone(),
+            initial: names(&self.initial),
+            accepting: names(&self.accepting),
+            transitions: self
+                .transitions
+                .iter()
+                .map(|t| {
+                    let label = match t.weight {
+                        Some(weight) => format!("{}:{}", t.label, weight),
+                        None => t.label.clone(),
+                    };
+                    (self.states[t.from].clone(), label, self.states[t.to].clone())
+                })
+                .collect(),
+        };
+        serde_json::to_string(&json).unwrap()
+    }
+
+    //add a state unless it already exists
+    fn register_state(&mut self, label: &str) {
+        if !self.states.iter().any(|s| s == label) {
+            self.states.push(label.to_string());
+        }
+    }
+
+    //regexes matching the node and edge declarations of a tikz picture
+    fn tikz_regexes() -> (Regex, Regex) {
+        let state_re = Regex::new(
+            r"\\node\[(?P<attrs>[^\]]*)\]\s*at\s*\([^)]+\)\s*\((?P<id>\w+)\)\s*\{\$(?P<name>[^$]+)\$\}",
+        )
+        .unwrap();
+        let edge_re =
+            Regex::new(r"\((?P<from>\w+)\)\s*edge.*?\{\$(?P<label>[^$]+)\$\}\s*\((?P<to>\w+)\)")
+                .unwrap();
+        (state_re, edge_re)
+    }
+
+    //lines declaring a node or an edge which the parsing regexes do not match,
+    //numbered from 1
+    fn malformed_tikz_lines<'a>(
+        input: &'a str,
+        state_re: &Regex,
+        edge_re: &Regex,
+    ) -> Vec<(usize, &'a str)> {
+        let edge_declaration_re = Regex::new(r"\(\w+\)\s*edge").unwrap();
+        input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                (line.contains("\\node") && !state_re.is_match(line))
+                    || (edge_declaration_re.is_match(line) && !edge_re.is_match(line))
+            })
+            .map(|(i, line)| (i + 1, line))
+            .collect()
     }
 
     /// Returns the alphabet of the NFA
@@ -286,6 +684,84 @@ impl Nfa {
         letters
     }
 
+    /// number of distinct letters labelling some transition
+    pub fn alphabet_size(&self) -> usize {
+        self.get_alphabet().len()
+    }
+
+    /// Returns the distinct letters labelling a transition leaving `state`, sorted.
+    pub fn enabled_letters(&self, state: State) -> Vec<&str> {
+        let mut letters = self
+            .transitions
+            .iter()
+            .filter(|t| t.from == state)
+            .map(|t| t.label.as_str())
+            .collect::<Vec<_>>();
+        letters.sort();
+        letters.dedup();
+        letters
+    }
+
+    /// Returns the distinct successors of `state` by `letter`, sorted.
+    pub fn successors_on(&self, state: State, letter: &str) -> Vec<State> {
+        let mut successors = self
+            .transitions
+            .iter()
+            .filter(|t| t.from == state && t.label == letter)
+            .map(|t| t.to)
+            .collect::<Vec<_>>();
+        successors.sort();
+        successors.dedup();
+        successors
+    }
+
+    /// The letter and state with the most distinct successors, and that number.
+    /// Computing flows is exponential in it.
+    /// Ties go to the smallest letter, then to the smallest state;
+    /// None if there is no transition.
+    pub fn max_out_degree(&self) -> Option<(Letter, State, usize)> {
+        self.letter_supports_sorted()
+            .into_iter()
+            .flat_map(|(letter, support)| {
+                support
+                    .out_degrees()
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(state, degree)| (letter.clone(), state, degree))
+            })
+            .min_by_key(|(letter, state, degree)| (std::cmp::Reverse(*degree), letter.clone(), *state))
+    }
+
+    /// Returns the distinct successors of `state` by `letter`, sorted,
+    /// together with their probabilities.
+    /// Transitions without explicit weight count as weight 1,
+    /// and weights are normalized to sum up to 1.
+    /// The parsers refuse zero weights and states mixing weighted and unweighted
+    /// transitions on a letter, hence the result is a distribution.
+    pub fn weighted_successors(&self, state: State, letter: &str) -> Vec<(State, f64)> {
+        let mut successors: Vec<(State, f64)> = Vec::new();
+        for t in self
+            .transitions
+            .iter()
+            .filter(|t| t.from == state && t.label == letter)
+        {
+            match successors.iter_mut().find(|(to, _)| *to == t.to) {
+                Some(entry) => {
+                    if let Some(weight) = t.weight {
+                        entry.1 = weight;
+                    }
+                }
+                None => successors.push((t.to, t.weight.unwrap_or(1.0))),
+            }
+        }
+        successors.sort_by_key(|&(to, _)| to);
+        let total: f64 = successors.iter().map(|(_, weight)| weight).sum();
+        successors
+            .into_iter()
+            .map(|(to, weight)| (to, weight / total))
+            .collect()
+    }
+
     #[allow(dead_code)]
     pub fn add_transition_by_index1(&mut self, from: State, to: State, label: char) {
         self.check_state(from);
@@ -294,6 +770,7 @@ impl Nfa {
             from,
             label: label.to_string(),
             to,
+            weight: None,
         });
     }
 
@@ -305,10 +782,157 @@ impl Nfa {
             from,
             label: label.to_string(),
             to,
+            weight: None,
         });
     }
 
+    /// Panics if a state does not exist, see `try_add_transition`.
     pub fn add_transition(&mut self, from: &str, to: &str, label: &str) {
+        self.try_add_transition(from, to, label).expect("State not found");
+    }
+
+    /// Adds a transition between existing states, the error names the first missing one.
+    pub fn try_add_transition(&mut self, from: &str, to: &str, label: &str) -> Result<(), String> {
+        for state in [from, to] {
+            if !self.states.iter().any(|q| q == state) {
+                return Err(format!("unknown state '{}'", state));
+            }
+        }
+        self.add_weighted_transition(from, to, label, None);
+        Ok(())
+    }
+
+    /// Adds several transitions at once, given as (from, to, label) triples.
+    pub fn add_transitions(&mut self, edges: &[(&str, &str, &str)]) {
+        for (from, to, label) in edges {
+            self.add_transition(from, to, label);
+        }
+    }
+
+    /// Removes transitions with the same source, label and target as a previous one.
+    pub fn dedup_transitions(&mut self) {
+        let mut seen = HashSet::new();
+        self.transitions
+            .retain(|t| seen.insert((t.from, t.label.clone(), t.to)));
+    }
+
+    /// Renames letters according to `map`, letters not in the map are kept.
+    /// Letters mapped to the same name are merged, the duplicate transitions this creates are removed.
+    pub fn relabel_letters(&mut self, map: &HashMap<String, String>) {
+        for transition in self.transitions.iter_mut() {
+            if let Some(label) = map.get(&transition.label) {
+                transition.label = label.clone();
+            }
+        }
+        self.dedup_transitions();
+    }
+
+    /// Merges the states of each block into the first one, which keeps its name and rank.
+    /// The merged state is initial, resp. accepting, if one state of the block is,
+    /// and starts with the sum of the initial configuration over the block,
+    /// omega if that sum is too large for a finite coefficient.
+    /// States in no block are kept, the duplicate transitions the merge creates are removed.
+    pub fn merge_states(&mut self, blocks: &[Vec<State>]) {
+        let mut representative: Vec<State> = (0..self.nb_states()).collect();
+        let mut merged = HashSet::new();
+        for block in blocks.iter().filter(|block| !block.is_empty()) {
+            for &q in block {
+                self.check_state(q);
+                assert!(merged.insert(q), "State {} is in two blocks", q);
+                representative[q] = block[0];
+            }
+        }
+        let kept: Vec<State> = (0..self.nb_states())
+            .filter(|&q| representative[q] == q)
+            .collect();
+        let mut rank = vec![0; self.nb_states()];
+        for (new, &q) in kept.iter().enumerate() {
+            rank[q] = new;
+        }
+        let old_to_new: Vec<State> = representative.iter().map(|&r| rank[r]).collect();
+        if let Some(config) = &self.initial_config {
+            let mut summed: HashMap<String, Coef> = HashMap::new();
+            for (q, name) in self.states.iter().enumerate() {
+                if let Some(&c) = config.get(name) {
+                    let sum = summed
+                        .entry(self.states[representative[q]].clone())
+                        .or_insert(C0);
+                    *sum = sum.saturating_add(c);
+                }
+            }
+            self.initial_config = Some(summed);
+        }
+        self.states = kept.iter().map(|&q| self.states[q].clone()).collect();
+        self.transitions.iter_mut().for_each(|t| {
+            t.from = old_to_new[t.from];
+            t.to = old_to_new[t.to];
+        });
+        self.initial = self.initial.iter().map(|&q| old_to_new[q]).collect();
+        self.accepting = self.accepting.iter().map(|&q| old_to_new[q]).collect();
+        self.dedup_transitions();
+    }
+
+    /// Merges bisimilar states, using the coarsest partition of the states such that
+    /// states of a block agree on being initial and accepting and,
+    /// for every letter, have successors in the same blocks.
+    /// Sheep cannot tell bisimilar states apart, so the answer of the solver is preserved.
+    /// Returns the number of states removed.
+    pub fn quotient_bisimulation(&mut self) -> usize {
+        let nb_states = self.nb_states();
+        let letters = self.get_alphabet();
+        let successors: Vec<Vec<Vec<State>>> = (0..nb_states)
+            .map(|q| letters.iter().map(|a| self.successors_on(q, a)).collect())
+            .collect();
+        let mut block: Vec<usize> = (0..nb_states)
+            .map(|q| 2 * self.initial.contains(&q) as usize + self.accepting.contains(&q) as usize)
+            .collect();
+        let mut nb_blocks = block.iter().unique().count();
+        loop {
+            //a state's signature is its block and the blocks of its successors
+            let mut ids = HashMap::new();
+            let refined: Vec<usize> = (0..nb_states)
+                .map(|q| {
+                    let signature = (
+                        block[q],
+                        successors[q]
+                            .iter()
+                            .map(|succ| succ.iter().map(|&r| block[r]).sorted().unique().collect_vec())
+                            .collect_vec(),
+                    );
+                    let id = ids.len();
+                    *ids.entry(signature).or_insert(id)
+                })
+                .collect();
+            block = refined;
+            if ids.len() == nb_blocks {
+                break;
+            }
+            nb_blocks = ids.len();
+        }
+        let blocks = (0..nb_states)
+            .map(|q| (block[q], q))
+            .into_group_map()
+            .into_values()
+            .filter(|block| block.len() > 1)
+            .sorted()
+            .collect_vec();
+        for block in &blocks {
+            info!(
+                "Merging bisimilar states {}",
+                block.iter().map(|&q| &self.states[q]).join(", ")
+            );
+        }
+        self.merge_states(&blocks);
+        nb_states - self.nb_states()
+    }
+
+    pub fn add_weighted_transition(
+        &mut self,
+        from: &str,
+        to: &str,
+        label: &str,
+        weight: Option<f64>,
+    ) {
         let from = self.get_state_index(from);
         let to = self.get_state_index(to);
         self.check_state(from);
@@ -317,9 +941,45 @@ impl Nfa {
             from,
             label: label.to_string(),
             to,
+            weight,
         });
     }
 
+    /// Splits a label of the form `a:0.3` into the letter and its weight.
+    /// Labels without a numerical suffix have no weight.
+    /// Fails on a weight which is zero, negative, infinite or not a number.
+    fn parse_weighted_label(label: &str) -> Result<(&str, Option<f64>), String> {
+        match label.rsplit_once(':') {
+            Some((letter, weight)) => match weight.trim().parse::<f64>() {
+                Ok(weight) if weight.is_finite() && weight > 0.0 => {
+                    Ok((letter.trim(), Some(weight)))
+                }
+                Ok(_) => Err(format!(
+                    "invalid weight in label '{}', weights are finite and positive",
+                    label
+                )),
+                Err(_) => Ok((label, None)),
+            },
+            None => Ok((label, None)),
+        }
+    }
+
+    /// Fails if a state has both weighted and unweighted transitions on a letter,
+    /// the unweighted ones would silently count as weight 1 against probabilities.
+    fn check_weights(&self) -> Result<(), String> {
+        let mut is_weighted: HashMap<(State, &str), bool> = HashMap::new();
+        for t in self.transitions.iter() {
+            let weighted = t.weight.is_some();
+            if *is_weighted.entry((t.from, t.label.as_str())).or_insert(weighted) != weighted {
+                return Err(format!(
+                    "state '{}' has weighted and unweighted transitions on letter '{}'",
+                    self.states[t.from], t.label
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// add a new state with given label
     /// returns the index of the new state or an Error if the state already exists
     pub fn add_state(&mut self, label: &str) -> Result<State,&str>{
@@ -357,25 +1017,70 @@ impl Nfa {
         self.accepting.insert(self.get_state_index(q));
     }
 
+    /// Mark as accepting every state whose name matches the regular expression,
+    /// anywhere in the name unless anchored, e.g. `_final$`.
+    /// Returns the number of matching states, including those already accepting.
+    pub fn mark_accepting_by_regex(&mut self, pattern: &str) -> Result<usize, regex::Error> {
+        let re = Regex::new(pattern)?;
+        let matching: Vec<State> = (0..self.nb_states())
+            .filter(|&q| re.is_match(&self.states[q]))
+            .collect();
+        self.accepting.extend(matching.iter());
+        Ok(matching.len())
+    }
+
+    /// The configuration the sheep start from, if one was given
+    /// instead of arbitrarily many sheep on every initial state.
+    /// States added after it was set start empty.
+    pub fn initial_config(&self) -> Option<Ideal> {
+        self.initial_config.as_ref().map(|config| {
+            Ideal::from_vec(
+                self.states
+                    .iter()
+                    .map(|q| config.get(q).copied().unwrap_or(C0))
+                    .collect(),
+            )
+        })
+    }
+
+    /// Sets the initial configuration, one coefficient per state in the current order.
+    pub fn set_initial_config(&mut self, config: &Ideal) {
+        assert_eq!(
+            config.dimension(),
+            self.nb_states(),
+            "initial configuration of the wrong size"
+        );
+        self.initial_config = Some(self.states.iter().cloned().zip(config.iter().copied()).collect());
+    }
+
     pub fn nb_states(&self) -> usize {
         self.states.len()
     }
 
+    /// same as nb_states
+    pub fn state_count(&self) -> usize {
+        self.nb_states()
+    }
+
     pub fn states_str(&self) -> String {
         format!("( {} )", self.states.join(" , "))
     }
 
+    /// initial states, in index order
     pub fn initial_states_str(&self) -> String {
         self.initial
             .iter()
+            .sorted()
             .map(|&i| self.states[i].as_str())
             .collect::<Vec<_>>()
             .join(" , ")
     }
 
+    /// accepting states, in index order
     pub fn accepting_states_str(&self) -> String {
         self.accepting
             .iter()
+            .sorted()
             .map(|&i| self.states[i].as_str())
             .collect::<Vec<_>>()
             .join(" , ")
@@ -394,13 +1099,162 @@ impl Nfa {
             .join("\n")
     }
 
-    pub fn get_edges(&self) -> HashMap<Letter, Graph> {
-        self.get_alphabet()
+    /// GraphML description of the automaton.
+    /// Nodes carry the `label`, `initial` and `accepting` data keys,
+    /// edges carry their `letter`, one edge per transition.
+    pub fn to_graphml(&self) -> String {
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#.to_string(),
+            r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#.to_string(),
+            r#"  <key id="initial" for="node" attr.name="initial" attr.type="boolean"/>"#.to_string(),
+            r#"  <key id="accepting" for="node" attr.name="accepting" attr.type="boolean"/>"#
+                .to_string(),
+            r#"  <key id="letter" for="edge" attr.name="letter" attr.type="string"/>"#.to_string(),
+            r#"  <graph id="nfa" edgedefault="directed">"#.to_string(),
+        ];
+        for (i, state) in self.states.iter().enumerate() {
+            lines.push(format!(
+                r#"    <node id="n{}"><data key="label">{}</data><data key="initial">{}</data><data key="accepting">{}</data></node>"#,
+                i,
+                xml_escape(state),
+                self.initial.contains(&i),
+                self.accepting.contains(&i)
+            ));
+        }
+        for t in self.transitions.iter() {
+            lines.push(format!(
+                r#"    <edge source="n{}" target="n{}"><data key="letter">{}</data></edge>"#,
+                t.from,
+                t.to,
+                xml_escape(&t.label)
+            ));
+        }
+        lines.push("  </graph>".to_string());
+        lines.push("</graphml>".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    /// answers the control problem when no computation is needed:
+    /// Some(true) if all initial states are accepting,
+    /// Some(false) if some non-accepting initial state has no outgoing transition,
+    /// None otherwise.
+    pub fn trivially_controllable(&self) -> Option<bool> {
+        if self.initial.iter().all(|q| self.accepting.contains(q)) {
+            Some(true)
+        } else if self
+            .initial
             .iter()
+            .any(|q| !self.accepting.contains(q) && self.transitions.iter().all(|t| t.from != *q))
+        {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the automaton accepts no word: no accepting state is both reachable
+    /// from an initial state and coreachable, in the union of the supports of all letters.
+    /// Arbitrarily many sheep can then not be brought to the accepting states,
+    /// unless no sheep is there to start with.
+    pub fn is_language_empty(&self) -> bool {
+        let support = self
+            .letter_supports_sorted()
+            .iter()
+            .fold(Graph::new(self.nb_states(), &[]), |union, (_, support)| {
+                union.union(support)
+            });
+        let initial: Vec<State> = self.initial.iter().cloned().collect();
+        let reachable = support.reachable_from(&initial);
+        let coreachable = support.coreachable_to(&self.final_states());
+        reachable.is_disjoint(&coreachable)
+    }
+
+    /// checks whether both nfas are equal up to a renaming of the states
+    /// preserving initial and accepting states and the labeled transitions.
+    ///
+    /// Backtracks over renamings, which is fine for small automata.
+    pub fn structurally_eq(&self, other: &Nfa) -> bool {
+        let self_edges = self.labeled_edges();
+        let other_edges = other.labeled_edges();
+        if self.nb_states() != other.nb_states()
+            || self.initial.len() != other.initial.len()
+            || self.accepting.len() != other.accepting.len()
+            || self_edges.len() != other_edges.len()
+        {
+            return false;
+        }
+        let mut renaming = vec![None; self.nb_states()];
+        let mut used = vec![false; other.nb_states()];
+        self.extend_renaming(other, &self_edges, &other_edges, &mut renaming, &mut used)
+    }
+
+    fn labeled_edges(&self) -> HashSet<(State, &str, State)> {
+        self.transitions
+            .iter()
+            .map(|t| (t.from, t.label.as_str(), t.to))
+            .collect()
+    }
+
+    //tries to map the first unmapped state of self, keeping the edges between mapped states
+    fn extend_renaming(
+        &self,
+        other: &Nfa,
+        self_edges: &HashSet<(State, &str, State)>,
+        other_edges: &HashSet<(State, &str, State)>,
+        renaming: &mut Vec<Option<State>>,
+        used: &mut Vec<bool>,
+    ) -> bool {
+        let q = match renaming.iter().position(|x| x.is_none()) {
+            None => return true,
+            Some(q) => q,
+        };
+        for p in 0..other.nb_states() {
+            if used[p]
+                || self.initial.contains(&q) != other.initial.contains(&p)
+                || self.accepting.contains(&q) != other.accepting.contains(&p)
+            {
+                continue;
+            }
+            renaming[q] = Some(p);
+            let consistent = self_edges.iter().all(|&(from, label, to)| {
+                match (renaming[from], renaming[to]) {
+                    (Some(from), Some(to)) => other_edges.contains(&(from, label, to)),
+                    _ => true,
+                }
+            });
+            if consistent {
+                used[p] = true;
+                if self.extend_renaming(other, self_edges, other_edges, renaming, used) {
+                    return true;
+                }
+                used[p] = false;
+            }
+            renaming[q] = None;
+        }
+        false
+    }
+
+    /// The support graph of every letter, iterated in the order of the letters.
+    pub fn get_edges(&self) -> BTreeMap<Letter, Graph> {
+        self.letter_supports_sorted().into_iter().collect()
+    }
+
+    /// The support graph of every letter, sorted by letter,
+    /// for the callers which iterate and must not depend on the hashing.
+    pub fn letter_supports_sorted(&self) -> Vec<(Letter, Graph)> {
+        self.get_alphabet()
+            .into_iter()
+            .sorted()
             .map(|action| (action.to_string(), self.get_support(action)))
             .collect()
     }
 
+    /// The transitions, in the order they were added.
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
     pub fn initial_states(&self) -> HashSet<State> {
         self.initial.clone()
     }
@@ -417,6 +1271,16 @@ impl Nfa {
             .expect("State not found")
     }
 
+    /// The weakly connected components of the union of the supports of all letters,
+    /// ordered by their smallest state.
+    pub fn connected_components(&self) -> Vec<Vec<State>> {
+        Graph::new(
+            self.nb_states(),
+            &self.transitions.iter().map(|t| (t.from, t.to)).collect::<Vec<_>>(),
+        )
+        .weakly_connected_components()
+    }
+
     pub fn get_support(&self, action: &str) -> crate::graph::Graph {
         Graph::new(
             self.states.len(),
@@ -442,17 +1306,41 @@ impl Nfa {
         input_type: &InputFormat,
         state_ordering: &StateOrdering,
     ) -> Self {
-        let mut nfa = match Self::read_file(path) {
-            Ok(content) => match input_type {
-                InputFormat::Tikz => Self::from_tikz(&content),
-                InputFormat::Dot => Self::from_dot(&content),
-            },
-            Err(e) => {
-                panic!("Error reading file '{}': '{}'", &path, e);
+        Self::load_from_file_with_dot_init_id(path, input_type, state_ordering, DOT_INIT_ID)
+    }
+
+    /// Same as `load_from_file`, DOT files use `dot_init_id` as artificial initial node.
+    pub fn load_from_file_with_dot_init_id(
+        path: &str,
+        input_type: &InputFormat,
+        state_ordering: &StateOrdering,
+        dot_init_id: &str,
+    ) -> Self {
+        Self::try_load_from_file_with_dot_init_id(path, input_type, state_ordering, dot_init_id)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Same as `load_from_file_with_dot_init_id`, fails if the file cannot be read or parsed.
+    pub fn try_load_from_file_with_dot_init_id(
+        path: &str,
+        input_type: &InputFormat,
+        state_ordering: &StateOrdering,
+        dot_init_id: &str,
+    ) -> Result<Self, String> {
+        let content = Self::read_file(path)
+            .map_err(|e| format!("Error reading file '{}': '{}'", &path, e))?;
+        let parsed = match input_type {
+            InputFormat::Tikz => Self::try_from_tikz(&content).map_err(|e| e.to_string()),
+            InputFormat::Dot => {
+                Self::try_from_dot_with_init_id(&content, dot_init_id).map_err(|e| e.to_string())
             }
+            InputFormat::Text => Self::from_text(&content),
+            #[cfg(feature = "serde")]
+            InputFormat::Json => Self::from_json(&content).map_err(|e| e.to_string()),
         };
+        let mut nfa = parsed.map_err(|e| format!("Error parsing '{}': {}", &path, e))?;
         nfa.sort(state_ordering);
-        nfa
+        Ok(nfa)
     }
 
     //allow useless pub
@@ -488,27 +1376,17 @@ impl Nfa {
 
     fn sort_states_topologically(&mut self) {
         //we want to sort states topologically
-        let mut successor_relation = HashMap::new();
-        for state in (0..self.nb_states()).collect::<Vec<_>>() {
-            //create aset at index state with state as its only element
-            successor_relation
-                .entry(state)
-                .or_insert_with(HashSet::new)
-                .insert(state);
-        }
-        loop {
-            let mut changed = false;
-            for t in self.transitions.iter() {
-                for successors in successor_relation.values_mut() {
-                    if successors.contains(&t.from) {
-                        changed |= successors.insert(t.to);
-                    }
-                }
-            }
-            if !changed {
-                break;
-            }
-        }
+        let support = Graph::new(
+            self.nb_states(),
+            &self
+                .transitions
+                .iter()
+                .map(|t| (t.from, t.to))
+                .collect::<Vec<_>>(),
+        );
+        let successor_relation = (0..self.nb_states())
+            .map(|state| (state, support.reachable_from(&[state])))
+            .collect::<HashMap<_, _>>();
         //reorder the vector state, first according to successor relation of its indices
         //and then according to alphabetical order
         let mut states_indices = (0..self.nb_states()).collect::<Vec<_>>();
@@ -529,16 +1407,63 @@ impl Nfa {
     }
 }
 
+/// Bounds of the automata generated by `Arbitrary`, kept small so that solving them is fast.
+#[cfg(feature = "testing")]
+const ARBITRARY_MAX_STATES: usize = 4;
+#[cfg(feature = "testing")]
+const ARBITRARY_LETTERS: [&str; 3] = ["a", "b", "c"];
+
+/// Random well-formed automata, with at least one initial and one accepting state,
+/// possibly without transitions.
+#[cfg(feature = "testing")]
+impl<'a> arbitrary::Arbitrary<'a> for Nfa {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let nb_states = u.int_in_range(1..=ARBITRARY_MAX_STATES)?;
+        let names = (0..nb_states).map(|q| format!("q{}", q)).collect::<Vec<_>>();
+        let mut nfa = Nfa::from_states(&names.iter().map(String::as_str).collect::<Vec<_>>());
+        let nb_letters = u.int_in_range(1..=ARBITRARY_LETTERS.len())?;
+        for _ in 0..u.int_in_range(0..=2 * nb_states * nb_letters)? {
+            let from = u.int_in_range(0..=nb_states - 1)?;
+            let to = u.int_in_range(0..=nb_states - 1)?;
+            let letter = ARBITRARY_LETTERS[u.int_in_range(0..=nb_letters - 1)?];
+            nfa.add_transition(&names[from], &names[to], letter);
+        }
+        nfa.dedup_transitions();
+        nfa.add_initial_by_index(u.int_in_range(0..=nb_states - 1)?);
+        nfa.add_final_by_index(u.int_in_range(0..=nb_states - 1)?);
+        for q in 0..nb_states {
+            if u.ratio(1u8, 4u8)? {
+                nfa.add_initial_by_index(q);
+            }
+            if u.ratio(1u8, 4u8)? {
+                nfa.add_final_by_index(q);
+            }
+        }
+        Ok(nfa)
+    }
+}
+
 impl fmt::Display for Nfa {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "NFA\n")?;
         writeln!(f, "States: {{ {} }}", self.states.join(" , "))?;
         writeln!(f, "Initial: {{ {} }}", self.initial_states_str())?;
         writeln!(f, "Accepting: {{ {} }}", self.accepting_states_str())?;
+        if let Some(config) = self.initial_config() {
+            writeln!(f, "Initial configuration: {}", config)?;
+        }
         writeln!(f, "Transitions:\n{}", self.transitions_str())
     }
 }
 
+//escape the characters with a special meaning in XML text and attributes
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -572,12 +1497,11 @@ mod test {
         nfa.add_transition_by_index1(1, 0, 'a');
 
         assert!(!nfa.is_complete());
-        nfa.complete(None);
+        nfa.complete(CompletionMode::SelfLoop);
         assert!(nfa.is_complete());
         assert!(nfa.transitions
                 .iter()
-                .filter(|t| t.from == 1 && t.label== "b" && t.to==1)
-                .next().is_some()
+                .any(|t| t.from == 1 && t.label== "b" && t.to==1)
         );
     }
     #[test]
@@ -589,24 +1513,137 @@ mod test {
         nfa.add_transition_by_index1(1, 0, 'a');
 
         assert!(!nfa.is_complete());
-        nfa.complete(Some(0));
+        nfa.complete(CompletionMode::Sink(0));
 
         assert!(nfa.is_complete());
 
         // check if 0 -a-> 0 exists
         assert!(nfa.transitions
                 .iter()
-                .filter(|t| t.from == 0 && t.label == "a" && t.to==0)
-                .next().is_some()
+                .any(|t| t.from == 0 && t.label == "a" && t.to==0)
         );
         // check if 1 -b-> 0 exists
         assert!(nfa.transitions
                 .iter()
-                .filter(|t| t.from == 1 && t.label == "b" && t.to == 0)
-                .next().is_some()
+                .any(|t| t.from == 1 && t.label == "b" && t.to == 0)
         );
     }
 
+    #[test]
+    fn complete_with_sink() {
+        let mut nfa = Nfa::from_states(&["0", "1"]);
+        nfa.add_final("1");
+        nfa.add_transition("0", "1", "a");
+        nfa.add_transition("1", "1", "b");
+
+        let sink = nfa.complete_with_sink().unwrap();
+        assert!(nfa.is_complete());
+        assert_eq!(nfa.states()[sink], SINK_LABEL);
+        assert!(!nfa.final_states().contains(&sink));
+        assert_eq!(nfa.successors_on(0, "b"), vec![sink]);
+        assert_eq!(nfa.successors_on(sink, "a"), vec![sink]);
+        assert_eq!(nfa.complete_with_sink(), None);
+    }
+
+    #[test]
+    fn states_after_sort() {
+        let mut nfa = Nfa::from_states(&["c", "a", "b"]);
+        nfa.add_initial("c");
+        nfa.add_final("b");
+        nfa.add_transitions(&[("c", "a", "x"), ("a", "b", "y"), ("b", "b", "x")]);
+        let named_transitions = |nfa: &Nfa| {
+            nfa.transitions
+                .iter()
+                .map(|t| (nfa.state_name(t.from).to_string(), nfa.state_name(t.to).to_string()))
+                .collect::<HashSet<_>>()
+        };
+        let expected = named_transitions(&nfa);
+        for (ordering, states) in [
+            (StateOrdering::Input, ["c", "a", "b"]),
+            (StateOrdering::Alphabetical, ["a", "b", "c"]),
+            (StateOrdering::Topological, ["c", "a", "b"]),
+        ] {
+            let mut sorted = nfa.clone();
+            sorted.sort(&ordering);
+            assert_eq!(sorted.states(), states);
+            assert_eq!(sorted.state_count(), 3);
+            for (i, name) in sorted.states().iter().enumerate() {
+                assert_eq!(sorted.state_name(i), *name);
+            }
+            assert_eq!(named_transitions(&sorted), expected);
+            assert_eq!(sorted.initial_states_str(), nfa.initial_states_str());
+        }
+    }
+
+    #[test]
+    fn trivially_controllable() {
+        let mut nfa = Nfa::from_states(&["0", "1", "2"]);
+        nfa.add_initial("0");
+        nfa.add_final("0");
+        nfa.add_transitions(&[("0", "1", "a"), ("1", "2", "a")]);
+        assert_eq!(nfa.trivially_controllable(), Some(true));
+
+        // 1 is initial, non-accepting and has outgoing transitions
+        nfa.add_initial("1");
+        assert_eq!(nfa.trivially_controllable(), None);
+
+        // 2 is initial, non-accepting and stuck
+        nfa.add_initial("2");
+        assert_eq!(nfa.trivially_controllable(), Some(false));
+    }
+
+    #[test]
+    fn is_language_empty() {
+        let mut nfa = Nfa::from_states(&["0", "1", "2"]);
+        nfa.add_initial("0");
+        nfa.add_final("2");
+        nfa.add_transitions(&[("0", "1", "a"), ("2", "1", "b")]);
+        assert!(nfa.is_language_empty());
+
+        // the accepting state becomes reachable
+        nfa.add_transitions(&[("1", "2", "b")]);
+        assert!(!nfa.is_language_empty());
+    }
+
+    #[test]
+    fn structurally_eq_relabeled() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r"]);
+        nfa.add_initial("p");
+        nfa.add_final("r");
+        nfa.add_transitions(&[("p", "q", "a"), ("p", "r", "a"), ("q", "r", "b"), ("r", "r", "a")]);
+
+        let mut relabeled = Nfa::from_states(&["2", "0", "1"]);
+        relabeled.add_initial("0");
+        relabeled.add_final("2");
+        relabeled.add_transitions(&[("0", "1", "a"), ("0", "2", "a"), ("1", "2", "b"), ("2", "2", "a")]);
+
+        assert!(nfa.structurally_eq(&nfa));
+        assert!(nfa.structurally_eq(&relabeled));
+        assert!(relabeled.structurally_eq(&nfa));
+    }
+
+    #[test]
+    fn structurally_eq_different() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r"]);
+        nfa.add_initial("p");
+        nfa.add_final("r");
+        nfa.add_transitions(&[("p", "q", "a"), ("q", "r", "b")]);
+
+        // same shape, letters swapped
+        let mut swapped = Nfa::from_states(&["p", "q", "r"]);
+        swapped.add_initial("p");
+        swapped.add_final("r");
+        swapped.add_transitions(&[("p", "q", "b"), ("q", "r", "a")]);
+        assert!(!nfa.structurally_eq(&swapped));
+
+        // same transitions, other accepting state
+        let mut accepting = Nfa::from_states(&["p", "q", "r"]);
+        accepting.add_initial("p");
+        accepting.add_final("q");
+        accepting.add_transitions(&[("p", "q", "a"), ("q", "r", "b")]);
+        assert!(!nfa.structurally_eq(&accepting));
+    }
+
     #[test]
     fn create() {
         let mut nfa = Nfa::from_states(&["toto", "titi"]);
@@ -616,6 +1653,222 @@ mod test {
         nfa.add_final("titi");
     }
 
+    #[test]
+    fn dedup_transitions() {
+        let mut nfa = Nfa::from_states(&["p", "q"]);
+        nfa.add_transitions(&[
+            ("p", "q", "a"),
+            ("p", "q", "a"),
+            ("q", "p", "a"),
+            ("q", "q", "b"),
+            ("p", "q", "a"),
+        ]);
+        assert_eq!(nfa.transitions.len(), 5);
+        let support_a = nfa.get_support("a").iter().cloned().collect::<HashSet<_>>();
+        let support_b = nfa.get_support("b").iter().cloned().collect::<HashSet<_>>();
+
+        nfa.dedup_transitions();
+        assert_eq!(nfa.transitions.len(), 3);
+        assert_eq!(nfa.transitions_str().lines().count(), 3);
+        assert_eq!(
+            nfa.get_support("a").iter().cloned().collect::<HashSet<_>>(),
+            support_a
+        );
+        assert_eq!(
+            nfa.get_support("b").iter().cloned().collect::<HashSet<_>>(),
+            support_b
+        );
+    }
+
+    #[test]
+    fn determinize() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r"]);
+        nfa.add_transitions(&[
+            ("p", "p", "a"),
+            ("p", "q", "a"),
+            ("q", "r", "b"),
+            ("p", "p", "b"),
+        ]);
+        nfa.add_initial("p");
+        nfa.add_final("r");
+        assert!(!nfa.is_deterministic());
+        assert_eq!(nfa.nondeterministic_pairs(), [(0, "a".to_string())]);
+
+        let dfa = nfa.determinize(10).unwrap();
+        assert!(dfa.is_deterministic());
+        assert_eq!(dfa.states, ["{p}", "{p,q}", "{p,r}"]);
+        assert_eq!(dfa.initial_states_str(), "{p}");
+        assert_eq!(dfa.accepting_states_str(), "{p,r}");
+        assert_eq!(dfa.successors_on(1, "b"), [2]);
+        assert_eq!(dfa.successors_on(2, "a"), [1]);
+        assert!(dfa.determinize(3).unwrap().is_deterministic());
+
+        assert!(nfa.determinize(2).is_err());
+    }
+
+    #[test]
+    fn determinize_names_clash() {
+        // {a,b} is the name of both the set of "a" and "b" and the set of "a,b"
+        let mut nfa = Nfa::from_states(&["i", "a", "b", "a,b"]);
+        nfa.add_transitions(&[("i", "a", "x"), ("i", "b", "x"), ("i", "a,b", "y")]);
+        nfa.add_initial("i");
+        nfa.add_final("a,b");
+        let dfa = nfa.determinize(10).unwrap();
+        assert_eq!(dfa.states, ["{i}", "{a,b}", "{a,b}#2"]);
+        assert_eq!(dfa.accepting_states_str(), "{a,b}#2");
+    }
+
+    #[test]
+    fn connected_components() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r", "s"]);
+        nfa.add_transitions(&[("p", "q", "a"), ("r", "q", "b"), ("s", "r", "a")]);
+        assert_eq!(nfa.connected_components(), vec![vec![0, 1, 2, 3]]);
+
+        let mut nfa = Nfa::from_states(&["p", "q", "r", "s"]);
+        nfa.add_transitions(&[("p", "r", "a"), ("q", "s", "b"), ("s", "s", "a")]);
+        assert_eq!(nfa.connected_components(), vec![vec![0, 2], vec![1, 3]]);
+    }
+
+    #[test]
+    fn merge_states() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r"]);
+        nfa.add_transitions(&[("p", "q", "a"), ("p", "r", "a"), ("r", "q", "b")]);
+        nfa.add_initial("p");
+        nfa.add_final("r");
+        nfa.set_initial_config(&Ideal::from_vec(vec![C0, Coef::Value(1), Coef::Value(2)]));
+        nfa.merge_states(&[vec![1, 2]]);
+        assert_eq!(nfa.states, ["p", "q"]);
+        assert_eq!(nfa.transitions.len(), 2);
+        assert_eq!(nfa.successors_on(1, "b"), [1]);
+        assert!(nfa.accepting.contains(&1));
+        assert_eq!(
+            nfa.initial_config(),
+            Some(Ideal::from_vec(vec![C0, Coef::Value(3)]))
+        );
+
+        let mut nfa = Nfa::from_states(&["p", "q"]);
+        nfa.set_initial_config(&Ideal::from_vec(vec![Coef::Value(200), Coef::Value(100)]));
+        nfa.merge_states(&[vec![0, 1]]);
+        assert_eq!(nfa.initial_config(), Some(Ideal::from_vec(vec![Coef::Omega])));
+    }
+
+    #[test]
+    fn quotient_bisimulation() {
+        let mut nfa = Nfa::from_states(&["0", "1", "2", "3"]);
+        nfa.add_transitions(&[
+            ("0", "1", "a"),
+            ("0", "2", "a"),
+            ("1", "3", "b"),
+            ("2", "3", "b"),
+            ("3", "3", "a"),
+            ("3", "3", "b"),
+        ]);
+        nfa.add_initial("0");
+        nfa.add_final("3");
+        let before = crate::solver::solve(&nfa, &crate::solver::SolverOutput::YesNo);
+        assert_eq!(nfa.quotient_bisimulation(), 1);
+        assert_eq!(nfa.nb_states(), 3);
+        assert_eq!(nfa.states, ["0", "1", "3"]);
+        let after = crate::solver::solve(&nfa, &crate::solver::SolverOutput::YesNo);
+        assert_eq!(before.is_controllable, after.is_controllable);
+        assert!(after.is_controllable);
+        // nothing left to merge
+        assert_eq!(nfa.quotient_bisimulation(), 0);
+    }
+
+    #[test]
+    fn alphabet_follows_transitions() {
+        let mut nfa = Nfa::from_states(&["p", "q"]);
+        nfa.add_transitions(&[("p", "q", "a"), ("q", "q", "b"), ("p", "p", "b")]);
+        assert_eq!(nfa.get_alphabet(), ["a", "b"]);
+        // no letter is stored apart from the transitions, none can linger
+        nfa.transitions.retain(|t| t.label != "b");
+        assert_eq!(nfa.get_alphabet(), ["a"]);
+        assert_eq!(nfa.alphabet_size(), 1);
+        assert_eq!(nfa.get_edges().keys().collect::<Vec<_>>(), ["a"]);
+    }
+
+    #[test]
+    fn relabel_letters() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r"]);
+        nfa.add_transitions(&[
+            ("p", "q", "a1"),
+            ("q", "r", "a1"),
+            ("p", "q", "a2"),
+            ("r", "p", "a2"),
+            ("r", "r", "b"),
+        ]);
+        let support = |nfa: &Nfa, a: &str| nfa.get_support(a).iter().cloned().collect::<HashSet<_>>();
+        let merged = &support(&nfa, "a1") | &support(&nfa, "a2");
+        let support_b = support(&nfa, "b");
+
+        nfa.relabel_letters(&HashMap::from([
+            ("a1".to_string(), "a".to_string()),
+            ("a2".to_string(), "a".to_string()),
+        ]));
+        assert_eq!(nfa.get_alphabet(), ["a", "b"]);
+        assert_eq!(support(&nfa, "a"), merged);
+        assert_eq!(support(&nfa, "b"), support_b);
+        // the two copies of p -> q are merged
+        assert_eq!(nfa.transitions.len(), 4);
+    }
+
+    //tags of a well-formed XML document are balanced
+    fn assert_balanced_tags(xml: &str) {
+        let mut open: Vec<&str> = Vec::new();
+        for tag in xml.split('<').skip(1).map(|x| x.split('>').next().unwrap()) {
+            if tag.starts_with('?') || tag.ends_with('/') {
+                continue;
+            }
+            match tag.strip_prefix('/') {
+                Some(name) => assert_eq!(open.pop(), Some(name)),
+                None => open.push(tag.split_whitespace().next().unwrap()),
+            }
+        }
+        assert!(open.is_empty());
+    }
+
+    #[test]
+    fn to_graphml() {
+        let mut nfa = Nfa::from_states(&["p", "q<r", "s"]);
+        nfa.add_initial("p");
+        nfa.add_final("s");
+        nfa.add_transitions(&[("p", "q<r", "a"), ("q<r", "s", "b"), ("s", "s", "a&b")]);
+        let xml = nfa.to_graphml();
+        assert!(xml.starts_with("<?xml"));
+        assert_balanced_tags(&xml);
+        assert_eq!(xml.matches("<node ").count(), 3);
+        assert_eq!(xml.matches("<edge ").count(), 3);
+        assert!(xml.contains(
+            r#"<node id="n0"><data key="label">p</data><data key="initial">true</data><data key="accepting">false</data></node>"#
+        ));
+        assert!(xml.contains(r#"<data key="label">q&lt;r</data>"#));
+        assert!(xml.contains(
+            r#"<edge source="n2" target="n2"><data key="letter">a&amp;b</data></edge>"#
+        ));
+    }
+
+    #[test]
+    fn max_out_degree() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r", "s"]);
+        assert_eq!(nfa.max_out_degree(), None);
+        nfa.add_transitions(&[
+            ("p", "q", "a"),
+            ("q", "r", "a"),
+            ("q", "p", "b"),
+            // the fan-out of r on b
+            ("r", "p", "b"),
+            ("r", "q", "b"),
+            ("r", "s", "b"),
+            ("r", "s", "b"),
+            ("s", "s", "a"),
+        ]);
+        assert_eq!(nfa.max_out_degree(), Some(("b".to_string(), 2, 3)));
+        nfa.add_transitions(&[("p", "p", "a"), ("p", "r", "a")]);
+        // ties go to the smallest letter
+        assert_eq!(nfa.max_out_degree(), Some(("a".to_string(), 0, 3)));
+    }
+
     #[test]
     fn parity() {
         let mut nfa = Nfa::from_size(2);
@@ -632,6 +1885,25 @@ mod test {
         assert!(letters.len() == 2);
     }
 
+    #[test]
+    fn parity_per_state_queries() {
+        let mut nfa = Nfa::from_size(3);
+        nfa.add_transition_by_index1(0, 1, 'a');
+        nfa.add_transition_by_index1(1, 0, 'a');
+        nfa.add_transition_by_index1(0, 0, 'b');
+        nfa.add_transition_by_index1(1, 1, 'b');
+        nfa.add_transition_by_index1(1, 1, 'b');
+        nfa.add_initial_by_index(0);
+        nfa.add_final_by_index(0);
+
+        assert_eq!(nfa.enabled_letters(0), vec!["a", "b"]);
+        assert_eq!(nfa.enabled_letters(1), vec!["a", "b"]);
+        assert!(nfa.enabled_letters(2).is_empty());
+        assert_eq!(nfa.successors_on(0, "a"), vec![1]);
+        assert_eq!(nfa.successors_on(1, "b"), vec![1]);
+        assert!(nfa.successors_on(0, "c").is_empty());
+    }
+
     #[test]
     fn a_b_star() {
         let mut nfa = Nfa::from_size(2);
@@ -641,6 +1913,68 @@ mod test {
         nfa.add_final_by_index(0);
     }
 
+    #[test]
+    fn tikz_malformed_lines() {
+        let (state_re, edge_re) = Nfa::tikz_regexes();
+        let input = r#"\begin{tikzpicture}[every edge/.style={draw,auto}]
+\node[initial,state] at (0,0) (q0) {$q0$};
+\node[accepting,state] at (2,0) (q1) {$q1$};
+\path[->]
+(q0) edge [above] node {$a$} (q1)
+(q1) edge [above] node {$b} (q0)
+;
+\end{tikzpicture}"#;
+        assert_eq!(
+            Nfa::malformed_tikz_lines(input, &state_re, &edge_re),
+            vec![(6, "(q1) edge [above] node {$b} (q0)")]
+        );
+        // the malformed edge is dropped
+        assert_eq!(Nfa::from_tikz(input).get_alphabet(), ["a"]);
+        for (name, content, _) in crate::examples::EXAMPLES {
+            assert!(
+                Nfa::malformed_tikz_lines(content, &state_re, &edge_re).is_empty(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn tikz_weighted_labels() {
+        let nfa = Nfa::from_tikz(
+            r#"
+\node[initial,thick,state] at (0,0) (q0) {$q0$};
+\node[thick,state] at (2,0) (q1) {$q1$};
+\node[thick,accepting,state] at (4,0) (q2) {$q2$};
+\path[->, thick, >=stealth]
+(q0) edge [above] node {$a:0.25, b$} (q1)
+(q0) edge [above] node {$a:0.75$} (q2)
+(q0) edge [above] node {$b$} (q2)
+;
+            "#,
+        );
+        let mut alphabet = nfa.get_alphabet();
+        alphabet.sort();
+        assert_eq!(alphabet, ["a", "b"]);
+        assert_eq!(nfa.weighted_successors(0, "a"), vec![(1, 0.25), (2, 0.75)]);
+        assert_eq!(nfa.weighted_successors(0, "b"), vec![(1, 0.5), (2, 0.5)]);
+        assert!(nfa.weighted_successors(1, "a").is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid weight in label 'a:-0.25'")]
+    fn tikz_negative_weight() {
+        Nfa::from_tikz(
+            r#"
+\node[initial,thick,state] at (0,0) (q0) {$q0$};
+\node[thick,state] at (2,0) (q1) {$q1$};
+\path[->, thick, >=stealth]
+(q0) edge [above] node {$a:-0.25$} (q1)
+;
+            "#,
+        );
+    }
+
     #[test]
     fn tikz() {
         let nfa = Nfa::from_tikz(
@@ -692,4 +2026,255 @@ mod test {
         succ_a_0.sort();
         assert_eq!(succ_a_0, vec![0, 1]);
     }
+
+    #[test]
+    fn from_text() {
+        let nfa = Nfa::from_text(
+            "# a small example
+initial: p
+p a q
+p -b-> r # arrow syntax
+q b:0.5 q
+
+accepting: q r
+",
+        )
+        .unwrap();
+        assert_eq!(nfa.states(), vec!["p", "q", "r"]);
+        assert_eq!(nfa.initial_states(), HashSet::from([0]));
+        let mut finals = nfa.final_states();
+        finals.sort();
+        assert_eq!(finals, vec![1, 2]);
+        assert_eq!(nfa.get_alphabet(), ["a", "b"]);
+        assert_eq!(
+            nfa.labeled_edges(),
+            HashSet::from([(0, "a", 1), (0, "b", 2), (1, "b", 1)])
+        );
+        assert_eq!(nfa.transitions[2].weight, Some(0.5));
+    }
+
+    #[test]
+    fn from_text_initial_config() {
+        let mut nfa = Nfa::from_text(
+            "init-config: 2, omega, 0
+p a q
+q b r
+",
+        )
+        .unwrap();
+        assert_eq!(
+            nfa.initial_config(),
+            Some(Ideal::from_vec(vec![Coef::Value(2), Coef::Omega, C0]))
+        );
+        // the configuration follows the states when they are reordered or added
+        nfa.apply_reordering(&[2, 0, 1]);
+        nfa.complete_with_sink();
+        assert_eq!(
+            nfa.initial_config(),
+            Some(Ideal::from_vec(vec![C0, Coef::Value(2), Coef::Omega, C0]))
+        );
+        assert_eq!(Nfa::from_text("p a q").unwrap().initial_config(), None);
+    }
+
+    #[test]
+    fn from_text_initial_config_wrong_size() {
+        assert_eq!(
+            Nfa::from_text("init-config: omega\np a q\n").err(),
+            Some("line 1: initial configuration of size 1 for 2 states".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_weights() {
+        for weight in ["NaN", "inf", "-1", "-0.5", "0", "0.0"] {
+            let error = Nfa::from_text(&format!("p a q\np a:{} q\n", weight)).unwrap_err();
+            assert!(error.starts_with("line 2: invalid weight"), "{}", error);
+        }
+        assert!(Nfa::from_text("p a:0.5 q\np b:1e3 q\n").is_ok());
+        assert_eq!(
+            Nfa::from_text("p a q\np a:0.5 r\n").err(),
+            Some("state 'p' has weighted and unweighted transitions on letter 'a'".to_string())
+        );
+    }
+
+    #[test]
+    fn tikz_and_dot_invalid_weights() {
+        let tikz = r#"
+\node[initial,thick,state] at (0,0) (q0) {$q0$};
+\node[thick,state] at (2,0) (q1) {$q1$};
+\path[->, thick, >=stealth]
+(q0) edge [above] node {$a:nan$} (q1)
+;
+"#;
+        assert!(matches!(Nfa::try_from_tikz(tikz), Err(NfaParseError::Syntax(_))));
+        let dot = r#"digraph NFA {
+            0 [label="0", root=true];
+            1 [label="1", peripheries=2];
+            0 -> 1 [label="a:-1"];
+        }"#;
+        assert!(matches!(
+            Nfa::try_from_dot_with_init_id(dot, DOT_INIT_ID),
+            Err(NfaParseError::Syntax(_))
+        ));
+        let dot = r#"digraph NFA {
+            0 [label="0", root=true];
+            1 [label="1", peripheries=2];
+            0 -> 1 [label="a:0.5"];
+            0 -> 0 [label="a"];
+        }"#;
+        assert!(matches!(
+            Nfa::try_from_dot_with_init_id(dot, DOT_INIT_ID),
+            Err(NfaParseError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn from_text_malformed_lines() {
+        let error = Nfa::from_text("initial: p\np -a- q\n").unwrap_err();
+        assert!(error.starts_with("line 2: malformed arrow"), "{}", error);
+        let error = Nfa::from_text("p a q\np a\n").unwrap_err();
+        assert!(error.starts_with("line 2: malformed transition"), "{}", error);
+    }
+
+    #[test]
+    fn dot_accepting_attributes() {
+        assert!(Nfa::is_dot_accepting_attribute("shape", "doublecircle"));
+        assert!(Nfa::is_dot_accepting_attribute("peripheries", "2"));
+        assert!(Nfa::is_dot_accepting_attribute("peripheries", "\"3\""));
+        assert!(!Nfa::is_dot_accepting_attribute("peripheries", "1"));
+        assert!(!Nfa::is_dot_accepting_attribute("peripheries", "two"));
+        assert!(!Nfa::is_dot_accepting_attribute("shape", "circle"));
+        assert!(!Nfa::is_dot_accepting_attribute("label", "doublecircle"));
+    }
+
+    #[test]
+    fn dot_initial_attributes() {
+        assert!(Nfa::is_dot_initial_attribute("root", "true"));
+        assert!(Nfa::is_dot_initial_attribute("style", "bold"));
+        assert!(Nfa::is_dot_initial_attribute("style", "\"filled, bold\""));
+        assert!(!Nfa::is_dot_initial_attribute("root", "false"));
+        assert!(!Nfa::is_dot_initial_attribute("style", "dashed"));
+        assert!(!Nfa::is_dot_initial_attribute("label", "bold"));
+    }
+
+    #[test]
+    fn from_dot_markers() {
+        let nfa = Nfa::from_dot(
+            r#"digraph NFA {
+                0 [label="0", root=true];
+                1 [label="1", style=bold];
+                2 [label="2", peripheries=2];
+                3 [label="3", shape=doublecircle];
+                4 [label="4", peripheries=1];
+                0 -> 2 [label="a"];
+                1 -> 3 [label="a"];
+                4 -> 4 [label="a"];
+            }"#,
+        );
+        let names = |states: HashSet<State>| {
+            states
+                .into_iter()
+                .map(|q| nfa.state_name(q).to_string())
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(nfa.initial_states()), vec!["0", "1"]);
+        assert_eq!(names(nfa.final_states().into_iter().collect()), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn from_dot_init_id() {
+        let input = r#"digraph NFA {
+                __start0 [label="", shape=none];
+                p [label="p"];
+                q [label="q", shape=doublecircle];
+                __start0 -> p;
+                p -> q [label="a"];
+            }"#;
+        let nfa = Nfa::from_dot_with_init_id(input, "__start0");
+        assert_eq!(nfa.states, ["p", "q"]);
+        assert_eq!(nfa.initial_states(), HashSet::from([0]));
+        assert_eq!(nfa.final_states(), vec![1]);
+        // with the default id, the start node is a state
+        assert_eq!(Nfa::from_dot(input).nb_states(), 3);
+    }
+
+    #[test]
+    fn letter_supports_sorted() {
+        let mut nfa = Nfa::from_states(&["p", "q"]);
+        nfa.add_transitions(&[("p", "q", "c"), ("q", "q", "a"), ("p", "p", "b"), ("q", "p", "a")]);
+        let supports = nfa.letter_supports_sorted();
+        let letters: Vec<&str> = supports.iter().map(|(a, _)| a.as_str()).collect();
+        assert_eq!(letters, vec!["a", "b", "c"]);
+        assert_eq!(supports[0].1, Graph::new(2, &[(1, 1), (1, 0)]));
+        for (letter, graph) in supports.iter() {
+            assert_eq!(nfa.get_edges()[letter], *graph);
+        }
+    }
+
+    #[test]
+    fn try_add_transition() {
+        let mut nfa = Nfa::from_states(&["p", "q"]);
+        assert_eq!(nfa.try_add_transition("p", "q", "a"), Ok(()));
+        assert_eq!(
+            nfa.try_add_transition("typo", "q", "a"),
+            Err("unknown state 'typo'".to_string())
+        );
+        assert_eq!(
+            nfa.try_add_transition("p", "r", "a"),
+            Err("unknown state 'r'".to_string())
+        );
+        assert_eq!(nfa.transitions_str(), "\tp --a--> q");
+    }
+
+    #[test]
+    #[should_panic]
+    fn add_transition_unknown_state() {
+        Nfa::from_states(&["p"]).add_transition("p", "q", "a");
+    }
+
+    #[test]
+    fn mark_accepting_by_regex() {
+        let mut nfa = Nfa::from_states(&["start", "a_final", "b", "b_final", "final_c"]);
+        nfa.add_final("b");
+        assert_eq!(nfa.mark_accepting_by_regex("_final$").ok(), Some(2));
+        assert_eq!(nfa.accepting_states_str(), "a_final , b , b_final");
+        assert_eq!(nfa.mark_accepting_by_regex("^nothing$").ok(), Some(0));
+        assert!(nfa.mark_accepting_by_regex("(").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let input = r#"{
+            "states": ["p", "q", "r"],
+            "initial": ["p"],
+            "accepting": ["r"],
+            "transitions": [["p", "a", "q"], ["q", "b:0.5", "r"], ["r", "a", "r"]]
+        }"#;
+        let nfa = Nfa::from_json(input).unwrap();
+        assert_eq!(nfa.states(), vec!["p", "q", "r"]);
+        assert_eq!(nfa.initial_states_str(), "p");
+        assert_eq!(nfa.accepting_states_str(), "r");
+        assert_eq!(nfa.transitions[1].label, "b");
+        assert_eq!(nfa.transitions[1].weight, Some(0.5));
+        let back = Nfa::from_json(&nfa.to_json()).unwrap();
+        assert_eq!(back.to_json(), nfa.to_json());
+        assert_eq!(back.transitions_str(), nfa.transitions_str());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_errors() {
+        let undeclared = r#"{ "states": ["p"], "initial": ["p"], "accepting": [],
+            "transitions": [["p", "a", "q"]] }"#;
+        assert_eq!(
+            Nfa::from_json(undeclared).err(),
+            Some(NfaParseError::UnknownState("q".to_string()))
+        );
+        assert!(matches!(
+            Nfa::from_json(r#"{ "states": ["p"] }"#),
+            Err(NfaParseError::Syntax(_))
+        ));
+    }
 }
@@ -30,6 +30,33 @@ fn get_partitions_rec(start_index: usize, current: &mut Vec<coef>, result: &mut
     }
 }
 
+/// Number of partitions returned by `get_partitions(x, len)`,
+/// that is the binomial coefficient (x + len - 1 choose len - 1), without enumerating them.
+/// Saturates at `u128::MAX`.
+pub fn partition_count(x: u16, len: usize) -> u128 {
+    if len == 0 {
+        return 0;
+    }
+    // (n choose k) with the smaller of both possible k
+    let n = x as u128 + len as u128 - 1;
+    let k = std::cmp::min(x as u128, len as u128 - 1);
+    let mut result: u128 = 1;
+    for i in 0..k {
+        // result * (n - i) is divisible by i + 1
+        result = match result.checked_mul(n - i) {
+            Some(product) => product / (i + 1),
+            None => return u128::MAX,
+        };
+    }
+    result
+}
+
+/// Number of transports returned by `get_transports(x, len)`:
+/// these are the same vectors as the partitions, in another order.
+pub fn transport_count(x: u16, len: usize) -> u128 {
+    partition_count(x, len)
+}
+
 #[cached]
 pub(crate) fn get_transports(c: coef, len: usize) -> Vec<Vec<coef>> {
     debug_assert!(len > 0);
@@ -65,7 +92,7 @@ fn get_transports_rec(
 mod test {
     use crate::partitions::get_partitions;
 
-    use super::get_transports;
+    use super::{get_transports, partition_count, transport_count};
 
     //test _get_partitions_rec on an example with start_index=0 current= [3,0,0] and result empty
     #[test]
@@ -116,4 +143,27 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn counts_match_enumeration() {
+        for x in 0..6u8 {
+            for len in 0..5 {
+                assert_eq!(
+                    partition_count(x as u16, len),
+                    get_partitions(x, len).len() as u128,
+                    "x = {}, len = {}",
+                    x,
+                    len
+                );
+                if len > 0 {
+                    assert_eq!(
+                        transport_count(x as u16, len),
+                        get_transports(x, len).len() as u128
+                    );
+                }
+            }
+        }
+        assert_eq!(partition_count(100, 3), 5151);
+        assert_eq!(partition_count(u16::MAX, usize::MAX), u128::MAX);
+    }
 }
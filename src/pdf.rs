@@ -0,0 +1,193 @@
+//! This module compiles the LaTeX report to PDF with an external TeX engine.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The TeX engine called by default.
+pub const TEX_CMD: &str = "pdflatex";
+
+/// Name of the LaTeX file written in the build directory.
+const TEX_FILE: &str = "solution.tex";
+
+/// A call to the TeX engine on the report written in `dir`.
+#[derive(Debug, PartialEq)]
+pub struct TexJob {
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub dir: PathBuf,
+    /// where the engine leaves the PDF
+    pub pdf: PathBuf,
+}
+
+impl TexJob {
+    /// Write the LaTeX source in `dir`, which is created if needed,
+    /// and prepare the command compiling it there.
+    pub fn prepare(tex_cmd: &str, latex: &str, dir: &Path) -> io::Result<TexJob> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(TEX_FILE), latex)?;
+        Ok(TexJob {
+            cmd: tex_cmd.to_string(),
+            args: tex_args(tex_cmd),
+            dir: dir.to_path_buf(),
+            pdf: dir.join(TEX_FILE).with_extension("pdf"),
+        })
+    }
+
+    /// Run the TeX engine, returns the path of the PDF.
+    pub fn run(&self) -> Result<&Path, String> {
+        let output = Command::new(&self.cmd)
+            .args(&self.args)
+            .current_dir(&self.dir)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => format!(
+                    "TeX compiler '{}' not found, set it with --tex-cmd or use --to tex and compile the report yourself",
+                    self.cmd
+                ),
+                _ => format!("failed to run '{}': {}", self.cmd, e),
+            })?;
+        if !output.status.success() {
+            //TeX engines report errors on stdout, the end of the log is the relevant part
+            let log = String::from_utf8_lossy(&output.stdout);
+            let tail = log.lines().rev().take(20).collect::<Vec<_>>();
+            return Err(format!(
+                "'{}' failed with {}:\n{}{}",
+                self.cmd,
+                output.status,
+                tail.into_iter().rev().collect::<Vec<_>>().join("\n"),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        if !self.pdf.exists() {
+            return Err(format!("'{}' produced no {}", self.cmd, self.pdf.display()));
+        }
+        Ok(&self.pdf)
+    }
+}
+
+//arguments of the engine, the file name last
+fn tex_args(tex_cmd: &str) -> Vec<String> {
+    let engine = Path::new(tex_cmd)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(tex_cmd);
+    let mut args: Vec<String> = match engine {
+        "pdflatex" | "xelatex" | "lualatex" => {
+            vec!["-interaction=nonstopmode".into(), "-halt-on-error".into()]
+        }
+        _ => vec![],
+    };
+    args.push(TEX_FILE.to_string());
+    args
+}
+
+/// Compile the LaTeX source in a temporary directory and copy the PDF to `output`.
+pub fn latex_to_pdf(tex_cmd: &str, latex: &str, output: &Path) -> Result<(), String> {
+    let dir = create_private_dir()
+        .map_err(|e| format!("couldn't create a temporary directory: {}", e))?;
+    let job = TexJob::prepare(tex_cmd, latex, &dir)
+        .map_err(|e| format!("couldn't write the report in {}: {}", dir.display(), e))?;
+    let result = job.run().and_then(|pdf| {
+        fs::copy(pdf, output)
+            .map(|_| ())
+            .map_err(|e| format!("couldn't write {}: {}", output.display(), e))
+    });
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+//a new directory in the temporary directory, readable by us only.
+//Creation fails if the name is taken, e.g. by a symlink planted by another user,
+//and another name is tried.
+fn create_private_dir() -> io::Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+    for attempt in 0..100 {
+        let dir = std::env::temp_dir().join(format!(
+            "shepherd-pdf-{}-{}-{}",
+            std::process::id(),
+            nanos,
+            attempt
+        ));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "all temporary directory names are taken",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("shepherd-pdf-test-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn prepare_writes_the_source() {
+        let dir = test_dir("prepare");
+        let job = TexJob::prepare("/usr/bin/pdflatex", "\\documentclass{article}", &dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("solution.tex")).unwrap(),
+            "\\documentclass{article}"
+        );
+        assert_eq!(
+            job,
+            TexJob {
+                cmd: "/usr/bin/pdflatex".to_string(),
+                args: vec![
+                    "-interaction=nonstopmode".to_string(),
+                    "-halt-on-error".to_string(),
+                    "solution.tex".to_string()
+                ],
+                dir: dir.clone(),
+                pdf: dir.join("solution.pdf"),
+            }
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn private_dirs_are_new() {
+        let first = create_private_dir().unwrap();
+        let second = create_private_dir().unwrap();
+        assert_ne!(first, second);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&first).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        fs::remove_dir(&first).unwrap();
+        fs::remove_dir(&second).unwrap();
+    }
+
+    #[test]
+    fn tex_args_depend_on_the_engine() {
+        assert_eq!(tex_args("tectonic"), vec!["solution.tex"]);
+        assert_eq!(tex_args("lualatex").len(), 3);
+    }
+
+    #[test]
+    fn missing_compiler_points_to_tex_output() {
+        let output = test_dir("missing").join("out.pdf");
+        let error = latex_to_pdf("/nonexistent/pdflatex", "", &output).unwrap_err();
+        assert!(error.contains("/nonexistent/pdflatex"));
+        assert!(error.contains("--to tex"));
+        assert!(!output.exists());
+    }
+}
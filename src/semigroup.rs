@@ -1,38 +1,197 @@
 use crate::coef::{coef, Coef, OMEGA};
 use crate::downset;
 use crate::flow::Flow;
+use crate::nfa::Nfa;
 use cached::proc_macro::cached;
 use itertools::Itertools;
 use log::debug;
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::collections::HashSet; // for distinct method
 use std::collections::VecDeque;
 use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// During the closure, dominated flows are removed each time the semigroup doubles in size
+/// since the last minimization, to bound peak memory.
+pub const DEFAULT_MINIMIZATION_GROWTH: f64 = 2.0;
 
 pub struct FlowSemigroup {
-    //invariant: all flows have the same dimension
-    flows: HashSet<Flow>,
+    //the flows, grouped by the positions of their omega entries.
+    //flow <= other requires the omega pattern of flow to be included in the one of other,
+    //hence coverage checks only need to scan compatible buckets.
+    //invariant: all flows have the same dimension and no bucket is empty
+    buckets: HashMap<Vec<bool>, HashSet<Flow>>,
+    //set when the closure stopped because of the bound on the number of flows
+    truncated: bool,
 }
 
 impl FlowSemigroup {
     pub fn new() -> Self {
         FlowSemigroup {
-            flows: HashSet::new(),
+            buckets: HashMap::new(),
+            truncated: false,
         }
     }
 
-    pub fn compute(flows: &HashSet<Flow>, maximal_finite_coordinate: coef) -> Self {
+    /// Closes the flows by product and iteration.
+    ///
+    /// If `max_flows` is set, the closure stops before the semigroup
+    /// grows beyond that many flows and the result is marked as truncated.
+    pub fn compute(
+        flows: &HashSet<Flow>,
+        maximal_finite_coordinate: coef,
+        max_flows: Option<usize>,
+    ) -> Self {
+        Self::compute_with_cancel(
+            flows,
+            maximal_finite_coordinate,
+            max_flows,
+            &AtomicBool::new(false),
+        )
+    }
+
+    /// The flow semigroup of an automaton: the closure of the all-omega flows
+    /// along the support of each letter, independently of any strategy.
+    pub fn from_nfa(nfa: &Nfa, max_value: coef) -> Self {
+        let flows: HashSet<Flow> = nfa
+            .letter_supports_sorted()
+            .iter()
+            .map(|(_, support)| Flow::from_graph(support, OMEGA))
+            .collect();
+        Self::compute(&flows, max_value, None)
+    }
+
+    /// Same as `compute`, but the closure stops as soon as `cancel` is set.
+    /// The flag is checked between flows and before each product of a parallel batch,
+    /// so that a large batch is cut short too.
+    /// In that case the partial (minimized) semigroup computed so far is returned.
+    pub fn compute_with_cancel(
+        flows: &HashSet<Flow>,
+        maximal_finite_coordinate: coef,
+        max_flows: Option<usize>,
+        cancel: &AtomicBool,
+    ) -> Self {
+        Self::compute_with(
+            flows,
+            maximal_finite_coordinate,
+            max_flows,
+            Some(DEFAULT_MINIMIZATION_GROWTH),
+            cancel,
+        )
+    }
+
+    /// Same as `compute`, with dominated flows removed during the closure
+    /// each time the semigroup grows by the factor `growth` since the last minimization,
+    /// or only at the end if `growth` is None.
+    /// The result does not depend on `growth`, only the peak memory and the time do.
+    /// Panics if `growth` is not above 1.
+    pub fn compute_with_minimization_growth(
+        flows: &HashSet<Flow>,
+        maximal_finite_coordinate: coef,
+        max_flows: Option<usize>,
+        growth: Option<f64>,
+    ) -> Self {
+        assert!(
+            growth.is_none_or(|growth| growth > 1.0),
+            "The minimization growth factor must be above 1"
+        );
+        Self::compute_with(
+            flows,
+            maximal_finite_coordinate,
+            max_flows,
+            growth,
+            &AtomicBool::new(false),
+        )
+    }
+
+    fn compute_with(
+        flows: &HashSet<Flow>,
+        maximal_finite_coordinate: coef,
+        max_flows: Option<usize>,
+        growth: Option<f64>,
+        cancel: &AtomicBool,
+    ) -> Self {
         let mut semigroup = FlowSemigroup::new();
         for flow in flows.iter() {
-            semigroup.flows.insert(flow.clone());
+            semigroup.insert(flow.clone());
         }
-        semigroup.close_by_product_and_iteration(maximal_finite_coordinate);
+        semigroup.truncated = max_flows.is_some_and(|max| semigroup.len() > max);
+        semigroup.close_by_product_and_iteration(
+            maximal_finite_coordinate,
+            max_flows,
+            growth,
+            cancel,
+        );
         semigroup
     }
 
+    /// Whether the closure was stopped by the bound on the number of flows,
+    /// in which case the semigroup may miss some flows.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
     #[allow(dead_code)]
     pub fn contains(&self, flow: &Flow) -> bool {
-        Self::is_covered(flow, &self.flows)
+        self.is_covered(flow)
+    }
+
+    /// Number of flows in the semigroup.
+    pub fn len(&self) -> usize {
+        self.buckets.values().map(HashSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    //the flows of all buckets, in no particular order
+    fn flows(&self) -> impl Iterator<Item = &Flow> {
+        self.buckets.values().flatten()
+    }
+
+    fn par_flows(&self) -> impl ParallelIterator<Item = &Flow> {
+        self.buckets.par_iter().flat_map(|(_, bucket)| bucket.par_iter())
+    }
+
+    /// The flows of the semigroup, sorted by their string representation
+    /// so that the output is deterministic.
+    pub fn sorted_flows(&self) -> Vec<&Flow> {
+        self.flows()
+            .sorted_by_key(|flow| flow.to_string())
+            .collect()
+    }
+
+    /// Write the semigroup to a file, one flow after the other.
+    pub fn dump(&self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        write!(file, "{}", self)
+    }
+
+    /// Insert a flow in the bucket of its omega pattern.
+    /// Returns true if the flow was not already present.
+    fn insert(&mut self, flow: Flow) -> bool {
+        self.buckets
+            .entry(flow.omega_pattern())
+            .or_default()
+            .insert(flow)
+    }
+
+    /// Remove a flow, dropping its bucket once empty.
+    fn remove(&mut self, flow: &Flow) -> bool {
+        let pattern = flow.omega_pattern();
+        let Some(bucket) = self.buckets.get_mut(&pattern) else {
+            return false;
+        };
+        let removed = bucket.remove(flow);
+        if bucket.is_empty() {
+            self.buckets.remove(&pattern);
+        }
+        removed
     }
 
     pub fn get_path_problem_solution(
@@ -41,8 +200,7 @@ impl FlowSemigroup {
     ) -> downset::DownSet {
         downset::DownSet::from_vec(
             &self
-                .flows
-                .iter()
+                .flows()
                 .map(|flow| flow.pre_image(target))
                 .collect::<Vec<_>>(),
         )
@@ -180,125 +338,245 @@ impl FlowSemigroup {
         );*/
     }
 
-    fn close_by_product_and_iteration(&mut self, maximal_finite_coordinate: coef) {
-        let mut to_process_mult: VecDeque<Flow> = self.flows.iter().cloned().collect();
-        let mut to_process_iter: VecDeque<Flow> = self
-            .flows
-            .iter()
-            .filter(|f| f.is_idempotent())
-            .cloned()
-            .collect();
-        //let mut processed = HashSet::<Flow>::new();
+    /// Closes the semigroup by product only, without iterating idempotent flows.
+    /// Returns true if some flow was added.
+    /// Unlike `compute`, dominated flows are not removed: an idempotent flow may be
+    /// dominated by a product and still have an iteration that is not.
+    pub fn close_by_product(&mut self, maximal_finite_coordinate: coef) -> bool {
+        let mut queues = ClosureQueues::new(self);
+        self.close_by_product_queued(
+            &mut queues,
+            maximal_finite_coordinate,
+            None,
+            None,
+            &AtomicBool::new(false),
+        )
+    }
+
+    /// Adds the iteration of every idempotent flow of the semigroup, in a single pass
+    /// and without closing by product afterwards.
+    /// Returns true if some flow was added. Dominated flows are not removed either.
+    pub fn apply_iterations(&mut self) -> bool {
+        let mut queues = ClosureQueues::new(self);
+        self.apply_iterations_queued(&mut queues, None, None, &AtomicBool::new(false))
+    }
+
+    /// The closure of the flows by product only, see `close_by_product`.
+    pub fn product_closure_only(flows: &HashSet<Flow>, maximal_finite_coordinate: coef) -> Self {
+        let mut semigroup = FlowSemigroup::new();
+        for flow in flows.iter() {
+            semigroup.insert(flow.clone());
+        }
+        semigroup.close_by_product(maximal_finite_coordinate);
+        semigroup
+    }
+
+    /// The flows together with the iterations of the idempotent ones, see `apply_iterations`.
+    /// The iteration of an idempotent flow is idempotent and its own iteration,
+    /// hence a single pass is closed under iteration.
+    pub fn iteration_closure_only(flows: &HashSet<Flow>) -> Self {
+        let mut semigroup = FlowSemigroup::new();
+        for flow in flows.iter() {
+            semigroup.insert(flow.clone());
+        }
+        semigroup.apply_iterations();
+        semigroup
+    }
+
+    fn close_by_product_and_iteration(
+        &mut self,
+        maximal_finite_coordinate: coef,
+        max_flows: Option<usize>,
+        growth: Option<f64>,
+        cancel: &AtomicBool,
+    ) {
+        let mut queues = ClosureQueues::new(self);
         loop {
-            let mut changed = false;
-            while !to_process_mult.is_empty() {
-                let flow = to_process_mult.pop_front().unwrap();
-                //print!(".");
-                //io::stdout().flush().unwrap();
-                debug!("\nClose by product processing flow\n{}\n", flow);
-                /*if Self::is_covered(&flow, &processed) {
-                    //debug!("Skipped inqueue\n{}", flow);
-                    continue;
-                }*/
-                //processed.insert(flow.clone());
-
-                let products: HashSet<Flow> = match maximal_finite_coordinate {
-                    0 | 1 => {
-                        let right_products = self
-                            .flows
-                            .par_iter() //.iter()
-                            .map(|other| &flow * other);
-                        let left_products = self
-                            .flows
-                            .par_iter() //.iter()
-                            .map(|other| other * &flow);
-                        left_products.chain(right_products).collect()
-                    }
-                    _ => {
-                        let right_products = self
-                            .flows
-                            .par_iter() //.iter()
-                            .flat_map(|other| {
-                                Self::get_products(&flow, other, maximal_finite_coordinate)
-                            });
-                        let left_products = self
-                            .flows
-                            .par_iter() //.iter()
-                            .flat_map(|other| {
-                                Self::get_products(other, &flow, maximal_finite_coordinate)
-                            });
-                        left_products.chain(right_products).collect()
+            if cancel.load(Ordering::Relaxed) {
+                debug!("Semigroup computation cancelled");
+                break;
+            }
+            if self.truncated {
+                debug!("Semigroup computation truncated at {} flows", self.len());
+                break;
+            }
+            let by_product = self.close_by_product_queued(
+                &mut queues,
+                maximal_finite_coordinate,
+                max_flows,
+                growth,
+                cancel,
+            );
+            let by_iteration = self.apply_iterations_queued(&mut queues, max_flows, growth, cancel);
+            if !by_product && !by_iteration {
+                break;
+            }
+        }
+        self.minimize();
+    }
+
+    //multiplies the flows waiting in the queue with all flows until the queue is empty,
+    //new idempotent flows are queued for iteration as well.
+    fn close_by_product_queued(
+        &mut self,
+        queues: &mut ClosureQueues,
+        maximal_finite_coordinate: coef,
+        max_flows: Option<usize>,
+        growth: Option<f64>,
+        cancel: &AtomicBool,
+    ) -> bool {
+        let mut changed = false;
+        while !queues.mult.is_empty() {
+            if cancel.load(Ordering::Relaxed) || self.truncated {
+                break;
+            }
+            let flow = queues.mult.pop_front().unwrap();
+            //print!(".");
+            //io::stdout().flush().unwrap();
+            debug!("\nClose by product processing flow\n{}\n", flow.to_string_aligned());
+            /*if Self::is_covered(&flow, &processed) {
+                //debug!("Skipped inqueue\n{}", flow);
+                continue;
+            }*/
+            //processed.insert(flow.clone());
+
+            //once cancelled, the products not started yet are skipped
+            let running = |_: &&Flow| !cancel.load(Ordering::Relaxed);
+            let products: HashSet<Flow> = match maximal_finite_coordinate {
+                0 | 1 => {
+                    let right_products = self
+                        .par_flows()
+                        .filter(running)
+                        .map(|other| &flow * other);
+                    let left_products = self
+                        .par_flows()
+                        .filter(running)
+                        .map(|other| other * &flow);
+                    left_products.chain(right_products).collect()
+                }
+                _ => {
+                    let right_products = self
+                        .par_flows()
+                        .filter(running)
+                        .flat_map(|other| {
+                            Self::get_products(&flow, other, maximal_finite_coordinate)
+                        });
+                    let left_products = self
+                        .par_flows()
+                        .filter(running)
+                        .flat_map(|other| {
+                            Self::get_products(other, &flow, maximal_finite_coordinate)
+                        });
+                    left_products.chain(right_products).collect()
+                }
+            };
+
+            //debug!("Products {:?}\n", products);
+            for product in products {
+                if !self.is_covered(&product) {
+                    if max_flows.is_some_and(|max| self.len() >= max) {
+                        self.truncated = true;
+                        break;
                     }
-                };
-
-                //debug!("Products {:?}\n", products);
-                for product in products {
-                    if !Self::is_covered(&product, &self.flows) {
-                        self.flows.insert(product.clone());
-                        debug!("\n\nAdded product, total {}", self.flows.len());
-                        if product.is_idempotent() {
-                            to_process_iter.push_back(product.clone());
-                        }
-                        to_process_mult.push_back(product);
-                        changed = true;
-                    } else {
-                        //debug!("\n\nSkipped product\n{}", product);
+                    self.insert(product.clone());
+                    debug!("\n\nAdded product, total {}", self.len());
+                    if product.is_idempotent() {
+                        queues.iter.push_back(product.clone());
                     }
-                }
-            }
-            while !to_process_iter.is_empty() {
-                let flow = to_process_iter.pop_front().unwrap();
-                debug_assert!(flow.is_idempotent());
-                //print!(".");
-                debug!("\nClose by product processing flow\n{}\n", flow);
-                let iteration = flow.iteration();
-                if !Self::is_covered(&iteration, &self.flows) {
-                    debug!("\n\nAdded iteration\n{}", iteration);
-                    self.flows.insert(iteration.clone());
-                    to_process_mult.push_back(iteration);
+                    queues.mult.push_back(product);
                     changed = true;
                 } else {
-                    //debug!("\n\nSkipped iteration\n{}", iteration);
+                    //debug!("\n\nSkipped product\n{}", product);
                 }
             }
-            if !changed {
+            self.minimize_if_grown(growth, &mut queues.minimized_size);
+        }
+        changed
+    }
+
+    //iterates the idempotent flows waiting in the queue, new flows are queued for product.
+    fn apply_iterations_queued(
+        &mut self,
+        queues: &mut ClosureQueues,
+        max_flows: Option<usize>,
+        growth: Option<f64>,
+        cancel: &AtomicBool,
+    ) -> bool {
+        let mut changed = false;
+        while !queues.iter.is_empty() {
+            if cancel.load(Ordering::Relaxed) || self.truncated {
                 break;
             }
+            let flow = queues.iter.pop_front().unwrap();
+            debug_assert!(flow.is_idempotent());
+            //print!(".");
+            debug!("\nIteration processing flow\n{}\n", flow.to_string_aligned());
+            let iteration = flow.iteration_cached(&mut queues.idempotents);
+            if !self.is_covered(&iteration) {
+                if max_flows.is_some_and(|max| self.len() >= max) {
+                    self.truncated = true;
+                    break;
+                }
+                debug!("\n\nAdded iteration\n{}", iteration.to_string_aligned());
+                self.insert(iteration.clone());
+                queues.mult.push_back(iteration);
+                changed = true;
+            } else {
+                //debug!("\n\nSkipped iteration\n{}", iteration);
+            }
+            self.minimize_if_grown(growth, &mut queues.minimized_size);
+        }
+        changed
+    }
+
+    //minimize once the semigroup grew by the factor growth since the last time.
+    //Flows still waiting in the queues are processed anyway,
+    //and the products of a removed flow are covered by those of a flow dominating it.
+    fn minimize_if_grown(&mut self, growth: Option<f64>, minimized_size: &mut usize) {
+        if let Some(growth) = growth {
+            if self.len() as f64 >= (*minimized_size).max(1) as f64 * growth {
+                self.minimize();
+                *minimized_size = self.len();
+            }
         }
-        self.minimize();
     }
 
-    fn is_covered(flow: &Flow, others: &HashSet<Flow>) -> bool {
+    fn is_covered(&self, flow: &Flow) -> bool {
         /*debug!(
             "Checking whether\n{} is covered by\n{}\n",
             flow,
-            others
-                .iter()
-                .map(Flow::to_string)
-                .collect::<Vec<_>>()
-                .join("\n")
+            self
         );*/
-        others.iter().any(|other| flow <= other)
+        let pattern = flow.omega_pattern();
+        self.buckets
+            .iter()
+            .filter(|(other_pattern, _)| {
+                pattern
+                    .iter()
+                    .zip(other_pattern.iter())
+                    .all(|(&is_omega, &other_is_omega)| !is_omega || other_is_omega)
+            })
+            .any(|(_, bucket)| bucket.iter().any(|other| flow <= other))
     }
 
     fn minimize(&mut self) {
         debug!("Minimizing semigroup");
-        let before = self.flows.len();
+        let before = self.len();
         //debug!("Before minimization\n{}", self);
         let mut to_remove = HashSet::<Flow>::new();
-        for flow in self.flows.iter() {
+        for flow in self.flows() {
             if to_remove.contains(flow) {
                 continue;
             }
-            if self.flows.iter().any(|other| flow < other) {
+            if self.flows().any(|other| flow < other) {
                 to_remove.insert(flow.clone());
             }
         }
         for flow in to_remove.iter() {
-            self.flows.remove(flow);
+            self.remove(flow);
         }
         //debug!("After minimization\n{}", self);
-        let after = self.flows.len();
+        let after = self.len();
         debug!(
             "Minimized semigroup from {} flows to {} flows",
             before, after
@@ -306,6 +584,31 @@ impl FlowSemigroup {
     }
 }
 
+//the flows still to be multiplied and iterated during the closure,
+//the size of the semigroup after the last minimization,
+//and the idempotent powers computed so far, dropped with the closure
+struct ClosureQueues {
+    mult: VecDeque<Flow>,
+    iter: VecDeque<Flow>,
+    minimized_size: usize,
+    idempotents: HashMap<Flow, Flow>,
+}
+
+impl ClosureQueues {
+    fn new(semigroup: &FlowSemigroup) -> Self {
+        ClosureQueues {
+            mult: semigroup.flows().cloned().collect(),
+            iter: semigroup
+                .flows()
+                .filter(|f| f.is_idempotent())
+                .cloned()
+                .collect(),
+            minimized_size: semigroup.len(),
+            idempotents: HashMap::new(),
+        }
+    }
+}
+
 #[cached]
 fn get_transports(
     left_edges: Vec<Coef>,
@@ -445,12 +748,11 @@ fn get_transports_rec(
 
 impl fmt::Display for FlowSemigroup {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut flows = self
-            .flows
+        let flows = self
+            .sorted_flows()
             .iter()
             .map(|flow| flow.to_string())
             .collect::<Vec<_>>();
-        flows.sort();
         write!(f, "{}", flows.join("\r\n"))
     }
 }
@@ -472,10 +774,23 @@ mod tests {
         let dim = 2_usize;
         let flowa = Flow::from_lines(&[&[OMEGA, C1], &[C0, OMEGA]]);
         let flows: HashSet<Flow> = [flowa].into();
-        let semigroup = FlowSemigroup::compute(&flows, dim as coef);
+        let semigroup = FlowSemigroup::compute(&flows, dim as coef, None);
         let flow_omega = Flow::from_entries(dim, dim, &[OMEGA, OMEGA, C0, OMEGA]);
         print!("\nsemigroup\n\n{}", semigroup);
-        assert!(semigroup.flows.contains(&flow_omega));
+        assert!(semigroup.flows().any(|flow| flow == &flow_omega));
+    }
+
+    #[test]
+    fn test_product_closure_then_iterations() {
+        let dim = 2_usize;
+        let flowa = Flow::from_lines(&[&[OMEGA, C1], &[C0, OMEGA]]);
+        let flows: HashSet<Flow> = [flowa].into();
+        let flow_omega = Flow::from_entries(dim, dim, &[OMEGA, OMEGA, C0, OMEGA]);
+        let mut semigroup = FlowSemigroup::product_closure_only(&flows, dim as coef);
+        assert!(!semigroup.contains(&flow_omega));
+        assert!(semigroup.apply_iterations());
+        assert!(semigroup.contains(&flow_omega));
+        assert!(FlowSemigroup::iteration_closure_only(&flows).contains(&flow_omega));
     }
 
     #[test]
@@ -484,29 +799,132 @@ mod tests {
         let flowa = Flow::from_lines(&[&[OMEGA, OMEGA, C0], &[OMEGA, OMEGA, C1], &[C0, C0, OMEGA]]);
         let flowb = Flow::from_lines(&[&[OMEGA, C0, C0], &[C0, C1, C0], &[C0, C0, OMEGA]]);
         let flows: HashSet<Flow> = [flowa.clone(), flowb.clone()].into();
-        let semigroup = FlowSemigroup::compute(&flows, dim);
+        let semigroup = FlowSemigroup::compute(&flows, dim, None);
         print!("\nsemigroup\n\n{}", semigroup);
         assert!(semigroup.contains(&flowa));
         assert!(semigroup.contains(&flowb));
     }
 
+    #[test]
+    fn test_flow_semigroup_compute2_periodic_minimization() {
+        let dim = 3;
+        let flowa = Flow::from_lines(&[&[OMEGA, OMEGA, C0], &[OMEGA, OMEGA, C1], &[C0, C0, OMEGA]]);
+        let flowb = Flow::from_lines(&[&[OMEGA, C0, C0], &[C0, C1, C0], &[C0, C0, OMEGA]]);
+        let flows: HashSet<Flow> = [flowa, flowb].into();
+        let at_the_end = FlowSemigroup::compute_with_minimization_growth(&flows, dim, None, None);
+        for growth in [1.01, 1.5, DEFAULT_MINIMIZATION_GROWTH] {
+            let periodic =
+                FlowSemigroup::compute_with_minimization_growth(&flows, dim, None, Some(growth));
+            assert_eq!(periodic.sorted_flows(), at_the_end.sorted_flows(), "{}", growth);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_minimization_growth_must_grow() {
+        FlowSemigroup::compute_with_minimization_growth(&HashSet::new(), 2, None, Some(1.0));
+    }
+
+    #[test]
+    fn test_from_nfa() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r"]);
+        nfa.add_transitions(&[("p", "q", "a"), ("p", "r", "a"), ("q", "r", "b"), ("r", "r", "b")]);
+        let semigroup = FlowSemigroup::from_nfa(&nfa, 3);
+        let flowa = Flow::from_lines(&[&[C0, OMEGA, OMEGA], &[C0, C0, C0], &[C0, C0, C0]]);
+        let flowb = Flow::from_lines(&[&[C0, C0, C0], &[C0, C0, OMEGA], &[C0, C0, OMEGA]]);
+        assert!(semigroup.contains(&flowa));
+        assert!(semigroup.contains(&flowb));
+        // a then b sends everything to r
+        assert!(semigroup.contains(&(&flowa * &flowb)));
+        assert!(!semigroup.is_truncated());
+    }
+
     #[test]
     fn test_flow_semigroup_compute3() {
         let dim = 3;
         let flowa = Flow::from_lines(&[&[OMEGA, C1, C0], &[OMEGA, C0, C1], &[C0, C0, OMEGA]]);
         let flowb = Flow::from_lines(&[&[OMEGA, C0, C0], &[C0, C1, C0], &[C0, C0, OMEGA]]);
         let flows: HashSet<Flow> = [flowa.clone(), flowb.clone()].into();
-        let semigroup = FlowSemigroup::compute(&flows, dim);
+        let semigroup = FlowSemigroup::compute(&flows, dim, None);
         print!("\nsemigroup\n\n{}", semigroup);
         assert!(semigroup.contains(&flowa));
         assert!(semigroup.contains(&flowb));
     }
 
+    #[test]
+    fn test_bucketed_coverage_agrees_with_linear() {
+        let dim = 3;
+        let flowa = Flow::from_lines(&[&[OMEGA, C1, C0], &[OMEGA, C0, C1], &[C0, C0, OMEGA]]);
+        let flowb = Flow::from_lines(&[&[OMEGA, C0, C0], &[C0, C1, C0], &[C0, C0, OMEGA]]);
+        let flows: HashSet<Flow> = [flowa.clone(), flowb.clone()].into();
+        let semigroup = FlowSemigroup::compute(&flows, dim, None);
+        let mut candidates = vec![flowa, flowb, Flow::from_entries(3, 3, &[OMEGA; 9])];
+        for left in semigroup.flows() {
+            candidates.push(left.clone());
+            for right in semigroup.flows() {
+                candidates.push(left * right);
+                candidates.extend(FlowSemigroup::get_products(left, right, dim));
+            }
+        }
+        for candidate in candidates.iter() {
+            let linear = semigroup.flows().any(|other| candidate <= other);
+            assert_eq!(semigroup.is_covered(candidate), linear, "\n{}", candidate);
+        }
+    }
+
+    #[test]
+    fn test_flow_semigroup_compute_cancelled() {
+        let flowa = Flow::from_lines(&[&[OMEGA, C1, C0], &[OMEGA, C0, C1], &[C0, C0, OMEGA]]);
+        let flowb = Flow::from_lines(&[&[OMEGA, C0, C0], &[C0, C1, C0], &[C0, C0, OMEGA]]);
+        let flowc = Flow::from_lines(&[&[OMEGA, C0, C0], &[C0, C0, C0], &[C0, C0, OMEGA]]);
+        let flows: HashSet<Flow> = [flowa.clone(), flowb.clone(), flowc].into();
+        let cancel = AtomicBool::new(true);
+        let semigroup = FlowSemigroup::compute_with_cancel(&flows, 3, None, &cancel);
+        //flowc is covered by flowb hence removed by minimization
+        assert_eq!(
+            semigroup.flows().cloned().collect::<HashSet<_>>(),
+            HashSet::from([flowa, flowb])
+        );
+    }
+
+    #[test]
+    fn test_flow_semigroup_compute_truncated() {
+        let flowa = Flow::from_lines(&[&[OMEGA, C1, C0], &[OMEGA, C0, C1], &[C0, C0, OMEGA]]);
+        let flowb = Flow::from_lines(&[&[OMEGA, C0, C0], &[C0, C1, C0], &[C0, C0, OMEGA]]);
+        let flows: HashSet<Flow> = [flowa, flowb].into();
+        let complete = FlowSemigroup::compute(&flows, 3, None);
+        assert!(!complete.is_truncated());
+        // the closure needs more than the two generators
+        let truncated = FlowSemigroup::compute(&flows, 3, Some(2));
+        assert!(truncated.is_truncated());
+        assert!(truncated.len() <= 2);
+        // not even the generators fit
+        let truncated = FlowSemigroup::compute(&flows, 3, Some(1));
+        assert!(truncated.is_truncated());
+    }
+
+    #[test]
+    fn test_dump() {
+        let flowa = Flow::from_lines(&[&[OMEGA, C1, C0], &[OMEGA, C0, C1], &[C0, C0, OMEGA]]);
+        let flowb = Flow::from_lines(&[&[OMEGA, C0, C0], &[C0, C1, C0], &[C0, C0, OMEGA]]);
+        let flows: HashSet<Flow> = [flowa, flowb].into();
+        let semigroup = FlowSemigroup::compute(&flows, 3, None);
+        let path = std::env::temp_dir().join("shepherd_test_dump_semigroup.txt");
+        semigroup.dump(&path).unwrap();
+        let dumped = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(semigroup.sorted_flows().len(), semigroup.len());
+        for flow in semigroup.sorted_flows() {
+            assert!(dumped.contains(&flow.to_string()), "missing\n{}", flow);
+        }
+        assert_eq!(dumped.split("\r\n").count(), semigroup.len());
+    }
+
     #[test]
     fn test_path_problem() {
         let flow = Flow::from_lines(&[&[C0, C1, C1], &[C0, C0, C0], &[C0, C0, C0]]);
         let flows: HashSet<Flow> = [flow].into();
-        let semigroup = FlowSemigroup::compute(&flows, 3);
+        let semigroup = FlowSemigroup::compute(&flows, 3, None);
         println!("semigroup\n\n{}", semigroup);
         let path_problem_solution = semigroup.get_path_problem_solution(&[1, 2]);
         println!("path_problem_solution\n{}", path_problem_solution);
@@ -526,7 +944,7 @@ mod tests {
             &[C0, C0, C0, C0, C0], //
         ]);
         let flows: HashSet<Flow> = [flow].into();
-        let semigroup = FlowSemigroup::compute(&flows, dim);
+        let semigroup = FlowSemigroup::compute(&flows, dim, None);
         println!("semigroup\n\n{}", semigroup);
         let path_problem_solution = semigroup.get_path_problem_solution(&[4]);
         println!("path_problem_solution\n{}", path_problem_solution);
@@ -1,17 +1,269 @@
-use crate::nfa::Nfa;
+use crate::coef::{coef, C0, OMEGA};
+use crate::flow::Flow;
+use crate::ideal::Ideal;
+use crate::nfa::{Letter, Nfa};
+use crate::semigroup::FlowSemigroup;
 use crate::strategy::Strategy;
+use itertools::Itertools;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use tera::{Context, Tera};
 
+/// Printed with the answer when the automaton was not complete.
+const INCOMPLETE_NOTE: &str =
+    "the automaton is incomplete, missing transitions are treated as loss.";
+
+/// Evidence for the answer of a solution, see `Solution::certificate`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Certificate {
+    /// a strategy defined on one of the sources of the solution
+    /// and closed under playing its letters,
+    /// and a run from the source to the accepting states which plays it:
+    /// each letter with the configuration it leads to
+    Controllable {
+        strategy: Strategy,
+        source: Ideal,
+        run: Vec<(Letter, Ideal)>,
+    },
+    /// not a certificate but a limit witness: for each source of the solution,
+    /// an idempotent flow of the semigroup; repeating the word it comes from keeps
+    /// some sheep of the source outside the accepting states forever.
+    /// Controllable automata have such flows too, on words the controller avoids,
+    /// hence this illustrates a negative answer rather than proving it.
+    LimitWitness { witnesses: Vec<(Ideal, Flow)> },
+    /// the answer was found without a strategy or a semigroup to extract evidence from,
+    /// or the semigroup was truncated
+    Unavailable,
+}
+
+impl Certificate {
+    /// Re-check the evidence against the automaton.
+    /// A strategy is checked with `solver::verify_strategy_from` on its source
+    /// and the run step by step along the edges of its letters.
+    /// Each flow of a limit witness is checked to be idempotent, to leave some sheep
+    /// of its source outside the accepting states, and to be realisable from the letter flows:
+    /// covered by the semigroup of the automaton, independently of any strategy.
+    pub fn verify(&self, nfa: &Nfa) -> bool {
+        match self {
+            Certificate::Controllable {
+                strategy,
+                source,
+                run,
+            } => {
+                let edges = nfa.get_edges();
+                let max_value = nfa.nb_states() as coef;
+                let mut current = source;
+                for (letter, next) in run {
+                    let plays = strategy
+                        .iter()
+                        .any(|(a, downset)| a == letter && downset.contains(current));
+                    let reaches = edges.get(letter).is_some_and(|graph| {
+                        current.image_under(graph, max_value).contains(next)
+                    });
+                    if !plays || !reaches {
+                        return false;
+                    }
+                    current = next;
+                }
+                strategy.is_defined_on(source)
+                    && current.is_below(&accepting_ideal(nfa))
+                    && crate::solver::verify_strategy_from(
+                        nfa,
+                        strategy,
+                        std::slice::from_ref(source),
+                    )
+                    .is_winning()
+            }
+            Certificate::LimitWitness { witnesses } => {
+                if witnesses.is_empty() {
+                    return false;
+                }
+                let targets = nfa.final_states();
+                let semigroup = FlowSemigroup::from_nfa(nfa, nfa.nb_states() as coef);
+                witnesses.iter().all(|(source, flow)| {
+                    flow.nb_rows == nfa.nb_states()
+                        && flow.is_idempotent()
+                        && !source.is_below(&flow.pre_image(&targets))
+                        && semigroup.contains(flow)
+                })
+            }
+            Certificate::Unavailable => false,
+        }
+    }
+}
+
 /// A solution to the population control problem.
 pub struct Solution {
     pub nfa: Nfa,
     pub is_controllable: bool,
     pub winning_strategy: Strategy,
-    pub semigroup: crate::semigroup::FlowSemigroup,
+    /// the configurations the answer is about, following the options of the solver:
+    /// controllable if the strategy is defined on one of them
+    pub sources: Vec<Ideal>,
+    pub semigroup: FlowSemigroup,
+    /// the solver hit the bound on the semigroup size,
+    /// hence a negative answer is inconclusive.
+    pub semigroup_truncated: bool,
+    /// time spent in each phase of the solver
+    pub statistics: crate::solver::Statistics,
+    /// every state had a transition for every letter,
+    /// otherwise the answer depends on the treatment of missing transitions
+    pub input_was_complete: bool,
 }
 
 impl Solution {
+    /// The non-accepting states from which arbitrarily many sheep cannot be controlled:
+    /// the omega-ideal on such a state is in the winning region of no letter.
+    /// Accepting states are never losing, even when no letter can be played from them.
+    pub fn losing_states(&self) -> Vec<&str> {
+        let region = self.winning_strategy.winning_region();
+        let accepting = self.nfa.final_states();
+        let dim = self.nfa.nb_states();
+        (0..dim)
+            .filter(|state| !accepting.contains(state))
+            .filter(|&state| {
+                let mut ideal = Ideal::new(dim, C0);
+                ideal.set(state, OMEGA);
+                !region.contains(&ideal)
+            })
+            .map(|state| self.nfa.state_name(state))
+            .collect()
+    }
+
+    /// Evidence for the answer, assembled from the winning strategy or the semigroup,
+    /// about the sources the solver was asked about.
+    /// A positive answer is certified from the first source the strategy is defined on,
+    /// with a shortest run, in the order of the letters and of the successors.
+    /// For a negative answer, the flow of each source is the first idempotent power,
+    /// in the order of `FlowSemigroup::sorted_flows`, which keeps sheep outside
+    /// the accepting states.
+    pub fn certificate(&self) -> Certificate {
+        if self.is_controllable {
+            return self
+                .sources
+                .iter()
+                .filter(|source| self.winning_strategy.is_defined_on(source))
+                .find_map(|source| {
+                    witness_run(&self.nfa, &self.winning_strategy, source).map(|run| {
+                        Certificate::Controllable {
+                            strategy: self.winning_strategy.clone(),
+                            source: source.clone(),
+                            run,
+                        }
+                    })
+                })
+                .unwrap_or(Certificate::Unavailable);
+        }
+        if self.semigroup_truncated || self.sources.is_empty() {
+            return Certificate::Unavailable;
+        }
+        let targets = self.nfa.final_states();
+        let flows = self.semigroup.sorted_flows();
+        let witnesses: Option<Vec<(Ideal, Flow)>> = self
+            .sources
+            .iter()
+            .map(|source| {
+                flows
+                    .iter()
+                    .map(|flow| flow.idempotent())
+                    .find(|flow| !source.is_below(&flow.pre_image(&targets)))
+                    .map(|flow| (source.clone(), flow))
+            })
+            .collect();
+        witnesses.map_or(Certificate::Unavailable, |witnesses| {
+            Certificate::LimitWitness { witnesses }
+        })
+    }
+
+    fn answer(&self) -> &'static str {
+        match (self.is_controllable, self.semigroup_truncated) {
+            (true, _) => "YES (controllable)",
+            (false, false) => "NO (uncontrollable)",
+            (false, true) => "UNKNOWN (semigroup size bound reached)",
+        }
+    }
+
+    /// One tab-separated line for batch runs:
+    /// `FILENAME  CONTROLLABLE=<bool>  STATES=<n>  LETTERS=<n>  SEMIGROUP=<n>  MS=<elapsed>`,
+    /// the elapsed time including parsing.
+    pub fn summary_line(&self, filename: &str) -> String {
+        let elapsed = self.statistics.parsing + self.statistics.total;
+        format!(
+            "{}\tCONTROLLABLE={}\tSTATES={}\tLETTERS={}\tSEMIGROUP={}\tMS={}",
+            filename,
+            self.is_controllable,
+            self.nfa.nb_states(),
+            self.nfa.alphabet_size(),
+            self.semigroup.len(),
+            elapsed.as_millis()
+        )
+    }
+
+    /// A Markdown report: the automaton as tables, the answer,
+    /// and one table per letter of the strategy, with a column per state.
+    pub fn as_markdown(&self) -> String {
+        let states = self.nfa.states();
+        let initial = self.nfa.initial_states();
+        let accepting = self.nfa.final_states();
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        let row = |cells: &[String]| format!("| {} |", cells.join(" | "));
+        let header = |cells: &[String]| {
+            format!("{}\n{}", row(cells), row(&vec!["---".to_string(); cells.len()]))
+        };
+
+        let mut lines = vec!["# Population control".to_string(), String::new()];
+        lines.push("## Automaton".to_string());
+        lines.push(String::new());
+        lines.push(header(&["State", "Initial", "Accepting"].map(String::from)));
+        for (q, name) in states.iter().enumerate() {
+            lines.push(row(&[
+                markdown_escape(name),
+                yes_no(initial.contains(&q)).to_string(),
+                yes_no(accepting.contains(&q)).to_string(),
+            ]));
+        }
+        lines.push(String::new());
+        lines.push(header(&["From", "Letter", "To"].map(String::from)));
+        for t in self.nfa.transitions() {
+            lines.push(row(&[
+                markdown_escape(states[t.from]),
+                markdown_escape(&t.label),
+                markdown_escape(states[t.to]),
+            ]));
+        }
+        lines.push(String::new());
+
+        lines.push("## Answer".to_string());
+        lines.push(String::new());
+        lines.push(format!("**{}**", self.answer()));
+        lines.push(String::new());
+        if !self.input_was_complete {
+            lines.push(format!("_Note: {}_", INCOMPLETE_NOTE));
+            lines.push(String::new());
+        }
+
+        lines.push("## Strategy".to_string());
+        let columns: Vec<String> = states.iter().map(|q| markdown_escape(q)).collect();
+        for (letter, downset) in self
+            .winning_strategy
+            .iter()
+            .sorted_by_key(|(letter, _)| *letter)
+        {
+            lines.push(String::new());
+            lines.push(format!("### Letter `{}`", letter));
+            lines.push(String::new());
+            if downset.is_empty() {
+                lines.push("Never played.".to_string());
+                continue;
+            }
+            lines.push(header(&columns));
+            for ideal in downset.iter_sorted() {
+                lines.push(row(&ideal.iter().map(|c| c.to_string()).collect::<Vec<_>>()));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
     pub fn as_latex(&self, tikz_path: Option<&str>) -> String {
         let template_content = include_str!("../latex/solution.template.tex");
 
@@ -50,10 +302,62 @@ impl Solution {
 
 impl fmt::Display for Solution {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let answer = match self.is_controllable {
-            true => "\tYES (controllable)".to_string(),
-            false => "\tNO (uncontrollable)".to_string(),
-        };
-        writeln!(f, "Answer:\n{}", answer)
+        writeln!(f, "Answer:\n\t{}", self.answer())?;
+        if !self.input_was_complete {
+            writeln!(f, "Note:\n\t{}", INCOMPLETE_NOTE)?;
+        }
+        Ok(())
+    }
+}
+
+//arbitrarily many sheep on every accepting state
+fn accepting_ideal(nfa: &Nfa) -> Ideal {
+    let mut accepting = Ideal::new(nfa.nb_states(), C0);
+    for q in nfa.final_states() {
+        accepting.set(q, OMEGA);
     }
+    accepting
+}
+
+//breadth-first search from the source for configurations below the accepting ideal,
+//playing the letters of the strategy and moving to maximal successor configurations.
+//Finite coordinates are bounded by the number of states, hence the search terminates.
+fn witness_run(nfa: &Nfa, strategy: &Strategy, source: &Ideal) -> Option<Vec<(Letter, Ideal)>> {
+    let accepting = accepting_ideal(nfa);
+    let edges = nfa.get_edges();
+    let max_value = nfa.nb_states() as coef;
+    let mut parents: HashMap<Ideal, (Ideal, Letter)> = HashMap::new();
+    let mut queue = VecDeque::from([source.clone()]);
+    while let Some(current) = queue.pop_front() {
+        if current.is_below(&accepting) {
+            let mut run = Vec::new();
+            let mut ideal = current;
+            while let Some((parent, letter)) = parents.get(&ideal) {
+                run.push((letter.clone(), ideal));
+                ideal = parent.clone();
+            }
+            run.reverse();
+            return Some(run);
+        }
+        for (letter, downset) in strategy.iter().sorted_by_key(|(letter, _)| *letter) {
+            let Some(graph) = edges.get(letter) else {
+                continue;
+            };
+            if !downset.contains(&current) {
+                continue;
+            }
+            for next in current.image_under(graph, max_value).iter_sorted() {
+                if *next != *source && !parents.contains_key(next) {
+                    parents.insert(next.clone(), (current.clone(), letter.clone()));
+                    queue.push_back(next.clone());
+                }
+            }
+        }
+    }
+    None
+}
+
+//pipes would end the table cell
+fn markdown_escape(s: &str) -> String {
+    s.replace('|', "\\|")
 }
@@ -2,56 +2,425 @@ use crate::coef::{coef, C0, OMEGA};
 use crate::flow;
 use crate::graph::Graph;
 use crate::ideal::Ideal;
+use crate::memoizer::Memoizer;
 use crate::nfa;
+use crate::partitions;
 use crate::semigroup::{self, FlowSemigroup};
 use crate::solution::Solution;
 use crate::strategy::Strategy;
 use clap::ValueEnum;
-use log::{debug, info};
-use std::collections::HashMap;
+use itertools::Itertools;
+use log::{debug, info, warn};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
 use std::collections::HashSet;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Flows of every (letter, ideal) pair met so far.
+/// The graph of each letter is fixed during a solve,
+/// so the letter identifies it.
+type ActionFlowsCache<'a> = Memoizer<
+    (nfa::Letter, Ideal),
+    HashSet<flow::Flow>,
+    Box<dyn Fn(&(nfa::Letter, Ideal)) -> HashSet<flow::Flow> + 'a>,
+>;
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum SolverOutput {
     YesNo,
     Strategy,
+    /// compute the maximal winning strategy and derive the yes/no answer from it
+    Both,
+    /// only decide controllability, as YesNo, and report it through the exit code
+    Quiet,
 }
 
-pub fn solve(nfa: &nfa::Nfa, output: &SolverOutput) -> Solution {
+/// How the initial states are combined into the question asked to the solver.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SourceSemantics {
+    /// controllable when starting with arbitrarily many sheep on every initial state
+    #[default]
+    AllInitial,
+    /// controllable when starting with arbitrarily many sheep on some single initial state
+    AnyInitial,
+}
+
+/// Optional settings of the solver, `solve` uses the defaults.
+#[derive(Debug, Clone, Default)]
+pub struct SolverOptions {
+    /// every semigroup computation stops at that many flows.
+    /// If this happens the solver gives up: the solution is marked as truncated
+    /// and is never claimed controllable.
+    pub max_flows: Option<usize>,
+    pub source_semantics: SourceSemantics,
+    /// bound on the finite coordinates, the number of states by default.
+    /// The strategy mode uses it as is, the yes/no mode tries all values below it.
+    /// A value smaller than the default may under-approximate the winning strategy.
+    pub max_value: Option<coef>,
+    /// the configuration the sheep start from, overriding the one of the automaton
+    /// and the source semantics.
+    /// Finite coefficients above the bound on finite coordinates are handled as omega.
+    pub initial_config: Option<Ideal>,
+    /// run on a single thread, e.g. to reproduce a crash; the answer is the same
+    pub serial: bool,
+    /// warn about the states with several successors on a letter, the answer is the same
+    pub warn_on_nondeterminism: bool,
+}
+
+/// Time spent in each phase of a solve, summed over all iterations.
+/// Parsing happens before the solver is called, the caller fills it in.
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    pub parsing: Duration,
+    /// flows of the actions allowed by the strategy
+    pub flows: Duration,
+    /// closure of the action flows under product and iteration
+    pub semigroup: Duration,
+    /// winning region of the path problem, from the semigroup
+    pub path_problem: Duration,
+    /// safe pre-images of the winning region and restriction of the strategy to them
+    pub restriction: Duration,
+    /// the whole solve, parsing excluded
+    pub total: Duration,
+}
+
+impl Statistics {
+    /// The solver phases, in the order they run, parsing excluded.
+    pub fn phases(&self) -> [(&'static str, Duration); 4] {
+        [
+            ("flows", self.flows),
+            ("semigroup", self.semigroup),
+            ("path problem", self.path_problem),
+            ("restriction", self.restriction),
+        ]
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<14}{:>12.3?}", "parsing", self.parsing)?;
+        for (phase, duration) in self.phases() {
+            writeln!(f, "{:<14}{:>12.3?}", phase, duration)?;
+        }
+        let other = self.total.saturating_sub(self.phases().iter().map(|p| p.1).sum());
+        writeln!(f, "{:<14}{:>12.3?}", "other", other)?;
+        write!(f, "{:<14}{:>12.3?}", "solver total", self.total)
+    }
+}
+
+/// Inputs the solver refuses to answer for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolverError {
+    /// no transition, hence no letter to build a strategy from
+    EmptyAlphabet,
+    /// the bound on finite coordinates must be at least 1
+    InvalidMaxValue,
+    /// the initial configuration does not have one coefficient per state
+    InvalidInitialConfig { expected: usize, actual: usize },
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolverError::EmptyAlphabet => write!(f, "the automaton has no transition"),
+            SolverError::InvalidMaxValue => write!(f, "the maximal value must be at least 1"),
+            SolverError::InvalidInitialConfig { expected, actual } => write!(
+                f,
+                "the initial configuration has {} coefficients instead of {}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+/// Outcome of `verify_strategy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationResult {
+    /// the strategy is defined on the initial configuration and is closed
+    Winning,
+    /// no letter is playable from arbitrarily many sheep on the initial states
+    NotDefinedOnInitial,
+    /// the strategy plays a letter the automaton does not have
+    UnknownLetter(nfa::Letter),
+    /// playing the letter from the ideal may lead outside of the winning region
+    NotClosed { letter: nfa::Letter, ideal: Ideal },
+}
+
+impl VerificationResult {
+    pub fn is_winning(&self) -> bool {
+        *self == VerificationResult::Winning
+    }
+}
+
+/// Check that a strategy computed elsewhere is winning:
+/// it must be defined on the initial omega-ideal, or on the initial configuration of the automaton,
+/// and one more step of the solver, with the number of states as bound on finite coordinates,
+/// must not restrict it any further.
+/// The witness of a failure is the first letter and ideal, in canonical order, which was removed.
+pub fn verify_strategy(nfa: &nfa::Nfa, strategy: &Strategy) -> VerificationResult {
+    verify_strategy_from(nfa, strategy, &get_sources(nfa, &SolverOptions::default()))
+}
+
+/// Same as `verify_strategy`, from the given configurations instead of the initial ones,
+/// e.g. the `sources` of a solution.
+pub fn verify_strategy_from(
+    nfa: &nfa::Nfa,
+    strategy: &Strategy,
+    sources: &[Ideal],
+) -> VerificationResult {
     let dim = nfa.nb_states();
-    let source = get_omega_ideal(
+    if !is_defined_on_some(strategy, sources) {
+        return VerificationResult::NotDefinedOnInitial;
+    }
+    let edges = nfa.get_edges();
+    if let Some(letter) = strategy.letters().into_iter().find(|&a| !edges.contains_key(a)) {
+        return VerificationResult::UnknownLetter(letter.clone());
+    }
+    let mut restricted = strategy.clone();
+    let mut cache = new_action_flows_cache(&edges);
+    let (changed, _) = update_strategy(
         dim,
-        &nfa.initial_states().iter().cloned().collect::<Vec<_>>(),
+        &mut restricted,
+        &nfa.final_states(),
+        &edges,
+        &mut cache,
+        dim as coef,
+        None,
+        &mut Statistics::default(),
     );
+    if !changed {
+        return VerificationResult::Winning;
+    }
+    for (letter, downset) in strategy.iter().sorted_by_key(|(letter, _)| *letter) {
+        let (_, kept) = restricted.iter().find(|(a, _)| *a == letter).unwrap();
+        if let Some(ideal) = downset.iter_sorted().find(|&ideal| !kept.contains(ideal)) {
+            return VerificationResult::NotClosed {
+                letter: letter.clone(),
+                ideal: ideal.clone(),
+            };
+        }
+    }
+    VerificationResult::Winning
+}
+
+/// Same as `solve_with_options`, but rejects automata without letters
+/// instead of reporting them uncontrollable.
+pub fn try_solve(
+    nfa: &nfa::Nfa,
+    output: &SolverOutput,
+    options: &SolverOptions,
+) -> Result<Solution, SolverError> {
+    if nfa.alphabet_size() == 0 {
+        return Err(SolverError::EmptyAlphabet);
+    }
+    if options.max_value == Some(0) {
+        return Err(SolverError::InvalidMaxValue);
+    }
+    if let Some(config) = &options.initial_config {
+        if config.dimension() != nfa.nb_states() {
+            return Err(SolverError::InvalidInitialConfig {
+                expected: nfa.nb_states(),
+                actual: config.dimension(),
+            });
+        }
+    }
+    Ok(solve_with_options(nfa, output, options))
+}
+
+pub fn solve(nfa: &nfa::Nfa, output: &SolverOutput) -> Solution {
+    solve_with_options(nfa, output, &SolverOptions::default())
+}
+
+/// Solve several automata, the solutions are in the same order.
+pub fn solve_all(nfas: &[nfa::Nfa], output: &SolverOutput) -> Vec<Solution> {
+    solve_all_with_options(nfas, output, &SolverOptions::default())
+}
+
+/// Same as `solve_all` with options shared by all automata.
+/// The automata are solved in parallel on the global thread pool, which the parallel parts
+/// of each solve also run on, so that threads are not oversubscribed.
+/// With `serial`, they are solved one after the other on a single thread.
+pub fn solve_all_with_options(
+    nfas: &[nfa::Nfa],
+    output: &SolverOutput,
+    options: &SolverOptions,
+) -> Vec<Solution> {
+    if options.serial {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .expect("Couldn't create a single thread pool");
+        let options = SolverOptions {
+            serial: false,
+            ..options.clone()
+        };
+        return pool.install(|| {
+            nfas.iter()
+                .map(|nfa| solve_with_options(nfa, output, &options))
+                .collect()
+        });
+    }
+    nfas.par_iter()
+        .map(|nfa| solve_with_options(nfa, output, options))
+        .collect()
+}
+
+pub fn solve_with_options(
+    nfa: &nfa::Nfa,
+    output: &SolverOutput,
+    options: &SolverOptions,
+) -> Solution {
+    solve_and_trace(nfa, output, options, None)
+}
+
+/// Same as `solve`, but also returns the strategy after each iteration of the fixpoint,
+/// to watch it shrink. The last one is the winning strategy of the solution;
+/// the trace is empty if the answer was found without iterating.
+pub fn solve_trace(nfa: &nfa::Nfa, output: &SolverOutput) -> (Solution, Vec<Strategy>) {
+    let mut trace = Vec::new();
+    let solution = solve_and_trace(nfa, output, &SolverOptions::default(), Some(&mut trace));
+    (solution, trace)
+}
+
+//strategies are only cloned into the trace if there is one
+fn solve_and_trace(
+    nfa: &nfa::Nfa,
+    output: &SolverOutput,
+    options: &SolverOptions,
+    trace: Option<&mut Vec<Strategy>>,
+) -> Solution {
+    if options.serial {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .expect("Couldn't create a single thread pool");
+        let options = SolverOptions {
+            serial: false,
+            ..options.clone()
+        };
+        return pool.install(|| solve_and_trace(nfa, output, &options, trace));
+    }
+    if options.warn_on_nondeterminism {
+        if let Some(warning) = nondeterminism_warning(nfa) {
+            warn!("{}", warning);
+        }
+    }
+    let components = nfa.connected_components();
+    if components.len() > 1 {
+        warn!(
+            "The automaton has {} disconnected parts: {}",
+            components.len(),
+            components
+                .iter()
+                .map(|component| component.iter().map(|&q| nfa.state_name(q)).join(", "))
+                .map(|names| format!("{{ {} }}", names))
+                .join(" ")
+        );
+    }
+    let start = Instant::now();
+    let mut statistics = Statistics::default();
+    let sources = get_sources(nfa, options);
+    let max_flows = options.max_flows;
+    let custom_source = options.initial_config.is_some() || nfa.initial_config().is_some();
+    if let (SolverOutput::YesNo | SolverOutput::Quiet, SourceSemantics::AllInitial, false) =
+        (output, options.source_semantics, custom_source)
+    {
+        let trivial_answer = match nfa.trivially_controllable() {
+            Some(is_controllable) => {
+                info!("The answer is trivial, skipping the semigroup computation");
+                Some(is_controllable)
+            }
+            None if nfa.is_language_empty() => {
+                info!("The automaton accepts no word, skipping the semigroup computation");
+                Some(false)
+            }
+            None => None,
+        };
+        if let Some(is_controllable) = trivial_answer {
+            statistics.total = start.elapsed();
+            return Solution {
+                nfa: nfa.clone(),
+                is_controllable,
+                winning_strategy: Strategy::empty(),
+                sources,
+                semigroup: FlowSemigroup::new(),
+                semigroup_truncated: false,
+                statistics,
+                input_was_complete: nfa.is_complete(),
+            };
+        }
+    }
+    if let Some((letter, state, degree)) = nfa.max_out_degree() {
+        info!(
+            "Largest out-degree: {} successors of state {} on letter {}",
+            degree,
+            nfa.state_name(state),
+            letter
+        );
+    }
+    let dim = nfa.nb_states();
     let final_states = nfa.final_states();
     let edges = nfa.get_edges();
     let letters = nfa.get_alphabet();
+    let max_value = options.max_value.unwrap_or(dim as coef);
     let (strategy, semigroup) = match output {
-        SolverOutput::Strategy => {
-            compute_maximal_winning_strategy(dim, &final_states, edges, &letters)
-        }
-        SolverOutput::YesNo => {
-            compute_control_problem_solution(dim, &source, &final_states, edges, &letters)
-        }
+        SolverOutput::Strategy | SolverOutput::Both => compute_maximal_winning_strategy(
+            dim,
+            &final_states,
+            edges,
+            &letters,
+            max_value,
+            max_flows,
+            trace,
+            &mut statistics,
+        ),
+        SolverOutput::YesNo | SolverOutput::Quiet => compute_control_problem_solution(
+            dim,
+            &sources,
+            &final_states,
+            edges,
+            &letters,
+            max_value,
+            max_flows,
+            trace,
+            &mut statistics,
+        ),
     };
-    let is_controllable = strategy.is_defined_on(&source);
+    let semigroup_truncated = semigroup.is_truncated();
+    //sheep already on accepting states need no letter, as in `Nfa::trivially_controllable`
+    let final_ideal = get_omega_ideal(dim, &final_states);
+    let already_accepted = sources.iter().any(|source| source.is_below(&final_ideal));
+    let is_controllable =
+        already_accepted || (!semigroup_truncated && is_defined_on_some(&strategy, &sources));
+    statistics.total = start.elapsed();
     Solution {
         nfa: nfa.clone(),
         is_controllable,
         winning_strategy: strategy,
+        sources,
         semigroup,
+        semigroup_truncated,
+        statistics,
+        input_was_complete: nfa.is_complete(),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn compute_maximal_winning_strategy(
     dim: usize,
     final_states: &[usize],
-    edges: HashMap<String, Graph>,
+    edges: BTreeMap<String, Graph>,
     letters: &[&str],
+    maximal_finite_value: coef,
+    max_flows: Option<usize>,
+    mut trace: Option<&mut Vec<Strategy>>,
+    statistics: &mut Statistics,
 ) -> (Strategy, FlowSemigroup) {
-    let maximal_finite_value = dim as coef;
-
     let mut strategy = Strategy::get_maximal_strategy(dim, letters);
+    let mut cache = new_action_flows_cache(&edges);
 
     let mut step = 1;
     loop {
@@ -64,26 +433,39 @@ fn compute_maximal_winning_strategy(
             &mut strategy,
             final_states,
             &edges,
+            &mut cache,
             maximal_finite_value,
+            max_flows,
+            statistics,
         );
+        if let Some(trace) = trace.as_mut() {
+            trace.push(strategy.clone());
+        }
 
-        if !changed {
+        if !changed || semigroup.is_truncated() {
             return (strategy, semigroup);
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn compute_control_problem_solution(
     dim: usize,
-    source: &Ideal,
+    sources: &[Ideal],
     final_states: &[usize],
-    edges: HashMap<String, Graph>,
+    edges: BTreeMap<String, Graph>,
     letters: &[&str],
+    max_value: coef,
+    max_flows: Option<usize>,
+    mut trace: Option<&mut Vec<Strategy>>,
+    statistics: &mut Statistics,
 ) -> (Strategy, FlowSemigroup) {
     let mut strategy = Strategy::get_maximal_strategy(dim, letters);
     let mut semigroup = FlowSemigroup::new();
+    let mut cache = new_action_flows_cache(&edges);
 
-    for maximal_finite_value in 1..dim as coef {
+    //at least one round, otherwise the maximal strategy would be returned as is
+    for maximal_finite_value in 1..=max_value.saturating_sub(1).max(1) {
         let mut step = 1;
         loop {
             //convert strategy to flows
@@ -98,50 +480,247 @@ fn compute_control_problem_solution(
                 &mut strategy,
                 final_states,
                 &edges,
+                &mut cache,
                 maximal_finite_value,
+                max_flows,
+                statistics,
             );
             semigroup = new_semigroup;
-            let result = strategy.is_defined_on(source);
+            if let Some(trace) = trace.as_mut() {
+                trace.push(strategy.clone());
+            }
+            if semigroup.is_truncated() {
+                return (strategy, semigroup);
+            }
+            let result = is_defined_on_some(&strategy, sources);
 
             if !changed || !result {
                 break;
             }
         }
-        if strategy.is_defined_on(source) {
+        if is_defined_on_some(&strategy, sources) {
             break;
         }
     }
     (strategy, semigroup)
 }
 
+//each phase is timed as a whole, the parallel loops inside are not instrumented
+#[allow(clippy::too_many_arguments)]
 fn update_strategy(
     dim: usize,
     strategy: &mut Strategy,
     final_states: &[usize],
-    edges: &HashMap<String, Graph>,
+    edges: &BTreeMap<String, Graph>,
+    cache: &mut ActionFlowsCache,
     maximal_finite_value: u8,
+    max_flows: Option<usize>,
+    statistics: &mut Statistics,
 ) -> (bool, FlowSemigroup) {
     let final_ideal = get_omega_ideal(dim, final_states);
-    let action_flows = compute_action_flows(strategy, edges);
+    let start = Instant::now();
+    let action_flows = compute_action_flows(strategy, cache);
+    statistics.flows += start.elapsed();
     debug!("\nAction flows:\n{}", flows_to_string(&action_flows));
     debug!(
         "Computing semigroup with maximal_finite_value {}",
         maximal_finite_value
     );
-    let semigroup = semigroup::FlowSemigroup::compute(&action_flows, maximal_finite_value);
+    let start = Instant::now();
+    let semigroup =
+        semigroup::FlowSemigroup::compute(&action_flows, maximal_finite_value, max_flows);
+    statistics.semigroup += start.elapsed();
     debug!("Semigroup:\n{}", semigroup);
     debug!("Computing winning set");
+    let start = Instant::now();
     let mut winning_downset = semigroup.get_path_problem_solution(final_states);
     winning_downset.insert(&final_ideal);
-    winning_downset.round_down(maximal_finite_value, dim);
     winning_downset.minimize();
+    winning_downset.round_down(maximal_finite_value, dim);
+    statistics.path_problem += start.elapsed();
     debug!("Winning set for the path problem:\n{}", winning_downset);
     debug!("Restricting strategy");
+    let start = Instant::now();
     let changed = strategy.restrict_to(winning_downset, edges, maximal_finite_value);
+    statistics.restriction += start.elapsed();
     debug!("Strategy after restriction:\n{}", strategy);
     (changed, semigroup)
 }
 
+/// Naive decision procedure, to cross-check `solve` on small automata.
+/// It computes, by a backward fixpoint over explicit configurations of at most `bound` sheep,
+/// the configurations from which the controller can bring every sheep to an accepting state,
+/// and checks that it contains every configuration of at most `bound` sheep on the initial states.
+/// A letter can only be played if every sheep has a successor.
+/// No flow is involved, but the number of configurations is exponential in the number of states.
+pub fn solve_bruteforce(nfa: &nfa::Nfa, bound: coef) -> bool {
+    let dim = nfa.nb_states();
+    let accepting = nfa.final_states();
+    let initial = nfa.initial_states();
+    let successors: Vec<Vec<Vec<usize>>> = nfa
+        .get_alphabet()
+        .iter()
+        .map(|a| (0..dim).map(|q| nfa.successors_on(q, a)).collect())
+        .collect();
+    let configurations: Vec<Vec<coef>> = (0..=bound)
+        .flat_map(|n| partitions::get_partitions(n, dim))
+        .collect();
+    let mut winning: HashSet<Vec<coef>> = configurations
+        .iter()
+        .filter(|&config| is_supported_by(config, |q| accepting.contains(&q)))
+        .cloned()
+        .collect();
+    loop {
+        let attracted: Vec<Vec<coef>> = configurations
+            .iter()
+            .filter(|&config| !winning.contains(config))
+            .filter(|&config| {
+                successors.iter().any(|successors| {
+                    is_supported_by(config, |q| !successors[q].is_empty())
+                        && next_configurations(config, successors)
+                            .iter()
+                            .all(|next| winning.contains(next))
+                })
+            })
+            .cloned()
+            .collect();
+        if attracted.is_empty() {
+            break;
+        }
+        winning.extend(attracted);
+    }
+    configurations
+        .iter()
+        .filter(|&config| is_supported_by(config, |q| initial.contains(&q)))
+        .all(|config| winning.contains(config))
+}
+
+//true if every state holding some sheep satisfies the predicate
+fn is_supported_by<F: Fn(usize) -> bool>(config: &[coef], pred: F) -> bool {
+    config.iter().enumerate().all(|(q, &n)| n == 0 || pred(q))
+}
+
+//all the ways the sheep of a configuration can move along the successors
+fn next_configurations(config: &[coef], successors: &[Vec<usize>]) -> Vec<Vec<coef>> {
+    let mut result = vec![vec![0; config.len()]];
+    for (q, &n) in config.iter().enumerate().filter(|&(_, &n)| n > 0) {
+        let moves = partitions::get_partitions(n, successors[q].len());
+        result = result
+            .iter()
+            .cartesian_product(moves.iter())
+            .map(|(next, moved)| {
+                let mut next = next.clone();
+                for (&r, &m) in successors[q].iter().zip(moved.iter()) {
+                    next[r] += m;
+                }
+                next
+            })
+            .collect();
+    }
+    result
+}
+
+/// Remove redundant letters and ideals from a winning strategy, for presentation,
+/// see `Strategy::minimize_for_source`.
+/// The result is checked with `verify_strategy`; if it is not winning,
+/// the strategy is left untouched.
+/// Returns true if the strategy was reduced.
+pub fn minimize_winning_strategy(
+    nfa: &nfa::Nfa,
+    strategy: &mut Strategy,
+    options: &SolverOptions,
+) -> bool {
+    let sources = get_sources(nfa, options);
+    let source = match sources.iter().find(|source| strategy.is_defined_on(source)) {
+        Some(source) => source,
+        None => return false,
+    };
+    let max_value = options.max_value.unwrap_or(nfa.nb_states() as coef);
+    let mut minimized = strategy.clone();
+    if !minimized.minimize_for_source(source, &nfa.get_edges(), max_value) {
+        return false;
+    }
+    match verify_strategy_from(nfa, &minimized, std::slice::from_ref(source)) {
+        VerificationResult::Winning => {
+            *strategy = minimized;
+            true
+        }
+        result => {
+            warn!("Keeping the strategy as is, its minimization is not winning: {:?}", result);
+            false
+        }
+    }
+}
+
+/// Let the controller of a winning strategy choose among fewer letters,
+/// see `Strategy::determinize_controller`.
+/// Dropping the ideals of a letter may lose the progress towards the accepting states,
+/// hence the result is checked with `verify_strategy`; if it is not winning,
+/// the strategy is left untouched.
+/// Returns true if the strategy was changed.
+pub fn determinize_winning_controller(
+    nfa: &nfa::Nfa,
+    strategy: &mut Strategy,
+    options: &SolverOptions,
+) -> bool {
+    let sources = get_sources(nfa, options);
+    let mut determinized = strategy.clone();
+    determinized.determinize_controller();
+    if determinized == *strategy {
+        return false;
+    }
+    match verify_strategy_from(nfa, &determinized, &sources) {
+        VerificationResult::Winning => {
+            *strategy = determinized;
+            true
+        }
+        result => {
+            warn!("Keeping the strategy as is, its determinization is not winning: {:?}", result);
+            false
+        }
+    }
+}
+
+//the ideals the strategy must be defined on: the initial configuration if any,
+//otherwise omega ideals depending on the semantics
+fn get_sources(nfa: &nfa::Nfa, options: &SolverOptions) -> Vec<Ideal> {
+    if let Some(config) = options.initial_config.clone().or_else(|| nfa.initial_config()) {
+        return vec![config];
+    }
+    let dim = nfa.nb_states();
+    let initial_states = nfa.initial_states().iter().cloned().collect::<Vec<_>>();
+    match options.source_semantics {
+        SourceSemantics::AllInitial => vec![get_omega_ideal(dim, &initial_states)],
+        SourceSemantics::AnyInitial => initial_states
+            .iter()
+            .map(|&q| get_omega_ideal(dim, &[q]))
+            .collect(),
+    }
+}
+
+fn is_defined_on_some(strategy: &Strategy, sources: &[Ideal]) -> bool {
+    sources.iter().any(|source| strategy.is_defined_on(source))
+}
+
+//lists the (state, letter) pairs with several successors, None if there is none
+fn nondeterminism_warning(nfa: &nfa::Nfa) -> Option<String> {
+    if nfa.is_deterministic() {
+        return None;
+    }
+    let pairs = nfa.nondeterministic_pairs();
+    if pairs.is_empty() {
+        //only several initial states, as usual for populations
+        return None;
+    }
+    Some(format!(
+        "The automaton is not deterministic, several successors for {}",
+        pairs
+            .iter()
+            .map(|(q, a)| format!("({}, {})", nfa.state_name(*q), a))
+            .join(", ")
+    ))
+}
+
 fn get_omega_ideal(dim: usize, states: &[usize]) -> Ideal {
     let mut ideal = Ideal::new(dim, C0);
     for state in states {
@@ -150,15 +729,20 @@ fn get_omega_ideal(dim: usize, states: &[usize]) -> Ideal {
     ideal
 }
 
-fn compute_action_flows(
-    strategy: &Strategy,
-    edges: &HashMap<nfa::Letter, Graph>,
-) -> HashSet<flow::Flow> {
+fn new_action_flows_cache(edges: &BTreeMap<nfa::Letter, Graph>) -> ActionFlowsCache<'_> {
+    Memoizer::new(Box::new(|(action, ideal)| {
+        flow::Flow::from_domain_and_edges(ideal, edges.get(action).unwrap())
+    }))
+}
+
+//letters and ideals are visited in a fixed order, so that the cache is filled deterministically.
+//The semigroup receives the flows as a set: the order in which it processes them is not fixed,
+//only the semigroup itself is.
+fn compute_action_flows(strategy: &Strategy, cache: &mut ActionFlowsCache) -> HashSet<flow::Flow> {
     let mut action_flows = HashSet::new();
-    for (action, downset) in strategy.iter() {
-        let edges_for_action = edges.get(action).unwrap();
-        for ideal in downset.ideals() {
-            let flows = flow::Flow::from_domain_and_edges(ideal, edges_for_action);
+    for (action, downset) in strategy.iter().sorted_by_key(|(action, _)| *action) {
+        for ideal in downset.iter_sorted() {
+            let flows = cache.get((action.clone(), ideal.clone()));
             for flow in flows {
                 action_flows.insert(flow);
             }
@@ -176,10 +760,31 @@ fn flows_to_string(flows: &HashSet<flow::Flow>) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::coef::{C1, C3};
+    use crate::downset::DownSet;
     use crate::flow::Flow;
     use crate::nfa::Nfa;
     use crate::strategy::Strategy;
 
+    #[test]
+    fn statistics_sum_to_total() {
+        let nfa = Nfa::from_tikz(include_str!("../examples/bottleneck-2.tikz"));
+        for output in [SolverOutput::Strategy, SolverOutput::YesNo] {
+            let statistics = solve(&nfa, &output).statistics;
+            let phases: Duration = statistics.phases().iter().map(|p| p.1).sum();
+            assert!(statistics.phases().iter().all(|p| p.1 <= statistics.total));
+            assert!(phases <= statistics.total);
+            // only the setup and the final check are not attributed to a phase
+            let untimed = statistics.total - phases;
+            assert!(
+                untimed <= Duration::from_millis(50).max(statistics.total / 2),
+                "{}",
+                statistics
+            );
+            assert_eq!(statistics.parsing, Duration::ZERO);
+        }
+    }
+
     //test compute_action_flows
     #[test]
     fn test_nfa_1() {
@@ -191,7 +796,7 @@ mod tests {
         nfa.add_transition_by_index1(1, 1, 'a');
         let strategy = Strategy::get_maximal_strategy(2, &["a"]);
         let edges = nfa.get_edges();
-        let action_flows = compute_action_flows(&strategy, &edges);
+        let action_flows = compute_action_flows(&strategy, &mut new_action_flows_cache(&edges));
         //a single action flow
         let flow: flow::Flow = Flow::from_entries(dim, dim, &[C0, OMEGA, C0, OMEGA]);
         assert_eq!(action_flows, HashSet::from([flow]));
@@ -207,6 +812,146 @@ mod tests {
         assert_eq!(grapha, grapha_from_nfa);
     }
 
+    #[test]
+    fn test_cached_action_flows() {
+        let edges = BTreeMap::from([(
+            "a".to_string(),
+            Graph::from_vec(3, vec![(0, 1), (1, 0), (1, 1), (2, 1), (2, 2)]),
+        )]);
+        let ideal = Ideal::from_vec(vec![C1, C3, OMEGA]);
+        let expected = Flow::from_domain_and_edges(&ideal, edges.get("a").unwrap());
+        let mut cache = new_action_flows_cache(&edges);
+        let key = ("a".to_string(), ideal);
+        assert_eq!(cache.get(key.clone()), expected);
+        assert_eq!(cache.hits(), 0);
+        // second lookup is served from the cache
+        assert_eq!(cache.get(key), expected);
+        assert_eq!(cache.hits(), 1);
+    }
+
+    #[test]
+    fn test_solve_trivial() {
+        let mut nfa = Nfa::from_states(&["0", "1", "2"]);
+        nfa.add_initial("0");
+        nfa.add_final("0");
+        nfa.add_transitions(&[("0", "1", "a"), ("1", "0", "a")]);
+        let solution = solve(&nfa, &SolverOutput::YesNo);
+        assert!(solution.is_controllable);
+        assert!(solution.semigroup.is_empty());
+
+        // 2 is initial, not accepting and has no outgoing transition
+        nfa.add_initial("2");
+        let solution = solve(&nfa, &SolverOutput::YesNo);
+        assert!(!solution.is_controllable);
+        assert!(solution.semigroup.is_empty());
+        assert!(!solve(&nfa, &SolverOutput::Strategy).is_controllable);
+    }
+
+    #[test]
+    fn test_accepting_initial_state_same_answer_in_all_modes() {
+        let mut nfa = Nfa::from_states(&["p", "q"]);
+        nfa.add_initial("p");
+        nfa.add_final("p");
+        nfa.add_transition("q", "q", "a");
+        let yes_no = solve(&nfa, &SolverOutput::YesNo);
+        assert!(yes_no.is_controllable);
+        for output in [SolverOutput::Strategy, SolverOutput::Both] {
+            assert_eq!(solve(&nfa, &output).is_controllable, yes_no.is_controllable);
+        }
+    }
+
+    #[test]
+    fn test_solve_empty_language() {
+        let mut nfa = Nfa::from_states(&["0", "1", "2"]);
+        nfa.add_initial("0");
+        nfa.add_final("2");
+        nfa.add_transitions(&[("0", "1", "a"), ("1", "0", "a"), ("2", "2", "a")]);
+        let solution = solve(&nfa, &SolverOutput::YesNo);
+        assert!(!solution.is_controllable);
+        assert!(solution.semigroup.is_empty());
+        assert!(!solve(&nfa, &SolverOutput::Strategy).is_controllable);
+    }
+
+    #[test]
+    fn test_try_solve_empty_alphabet() {
+        let mut nfa = Nfa::from_states(&["0", "1"]);
+        nfa.add_initial("0");
+        nfa.add_final("1");
+        assert_eq!(nfa.alphabet_size(), 0);
+        let result = try_solve(&nfa, &SolverOutput::YesNo, &SolverOptions::default());
+        assert_eq!(result.err(), Some(SolverError::EmptyAlphabet));
+
+        nfa.add_transition("0", "1", "a");
+        assert_eq!(nfa.alphabet_size(), 1);
+        let result = try_solve(&nfa, &SolverOutput::YesNo, &SolverOptions::default());
+        assert!(result.unwrap().is_controllable);
+    }
+
+    #[test]
+    fn test_explicit_max_value() {
+        let nfa = Nfa::from_tikz(crate::examples::EXAMPLE2);
+        let options = SolverOptions {
+            max_value: Some(nfa.nb_states() as coef),
+            ..Default::default()
+        };
+        for output in [SolverOutput::YesNo, SolverOutput::Strategy] {
+            let default = solve(&nfa, &output);
+            let explicit = solve_with_options(&nfa, &output, &options);
+            assert_eq!(explicit.is_controllable, default.is_controllable);
+            assert_eq!(explicit.winning_strategy, default.winning_strategy);
+        }
+        let options = SolverOptions {
+            max_value: Some(0),
+            ..Default::default()
+        };
+        let result = try_solve(&nfa, &SolverOutput::YesNo, &options);
+        assert_eq!(result.err(), Some(SolverError::InvalidMaxValue));
+    }
+
+    #[test]
+    fn test_source_semantics() {
+        // from 0 the sheep reach 1 with a, from 2 they are stuck in a loop
+        let mut nfa = Nfa::from_states(&["0", "1", "2"]);
+        nfa.add_initial("0");
+        nfa.add_initial("2");
+        nfa.add_final("1");
+        nfa.add_transitions(&[("0", "1", "a"), ("1", "1", "a"), ("2", "2", "a")]);
+        let any = SolverOptions {
+            source_semantics: SourceSemantics::AnyInitial,
+            ..Default::default()
+        };
+        for output in [SolverOutput::YesNo, SolverOutput::Strategy] {
+            assert!(!solve(&nfa, &output).is_controllable);
+            assert!(solve_with_options(&nfa, &output, &any).is_controllable);
+        }
+    }
+
+    #[test]
+    fn test_solve_truncated_semigroup() {
+        let mut nfa = Nfa::from_states(&["0", "1", "2"]);
+        nfa.add_initial("0");
+        nfa.add_final("1");
+        nfa.add_transitions(&[
+            ("0", "1", "a"),
+            ("0", "2", "a"),
+            ("1", "1", "a"),
+            ("1", "1", "b"),
+            ("2", "1", "b"),
+        ]);
+        for output in [SolverOutput::YesNo, SolverOutput::Strategy] {
+            let solution = solve(&nfa, &output);
+            assert!(!solution.semigroup_truncated);
+            assert!(solution.is_controllable);
+            let options = SolverOptions {
+                max_flows: Some(1),
+                ..Default::default()
+            };
+            let solution = solve_with_options(&nfa, &output, &options);
+            assert!(solution.semigroup_truncated);
+            assert!(!solution.is_controllable);
+        }
+    }
+
     #[test]
     fn test_nfa_2() {
         let dim = 2;
@@ -220,7 +965,7 @@ mod tests {
         nfa.add_transition_by_index1(1, 1, 'b');
         let strategy = Strategy::get_maximal_strategy(2, &["a", "b"]);
         let edges = nfa.get_edges();
-        let computed = compute_action_flows(&strategy, &edges);
+        let computed = compute_action_flows(&strategy, &mut new_action_flows_cache(&edges));
         //a single action flow
         assert_eq!(
             computed,
@@ -322,4 +1067,316 @@ mod tests {
         print!("{}", solution);
         assert!(solution.is_controllable);
     }
+
+    #[test]
+    fn test_solve_both_agrees_with_yes_no_and_strategy() {
+        let mut nfa = Nfa::from_size(3);
+        nfa.add_initial_by_index(0);
+        nfa.add_final_by_index(2);
+        nfa.add_transition_by_index1(0, 1, 'a');
+        nfa.add_transition_by_index1(1, 1, 'a');
+        nfa.add_transition_by_index1(0, 2, 'a');
+        nfa.add_transition_by_index1(2, 2, 'a');
+        nfa.add_transition_by_index1(0, 0, 'b');
+        nfa.add_transition_by_index1(1, 2, 'b');
+        nfa.add_transition_by_index1(2, 2, 'b');
+        let both = solve(&nfa, &SolverOutput::Both);
+        let yes_no = solve(&nfa, &SolverOutput::YesNo);
+        let strategy = solve(&nfa, &SolverOutput::Strategy);
+        assert_eq!(both.is_controllable, yes_no.is_controllable);
+        assert_eq!(both.is_controllable, strategy.is_controllable);
+        assert_eq!(both.winning_strategy, strategy.winning_strategy);
+    }
+
+    fn sink_nfa() -> Nfa {
+        // a moves the sheep from 0 to the target 1, b sends them to the sink 2
+        let mut nfa = Nfa::from_states(&["0", "1", "2"]);
+        nfa.add_initial("0");
+        nfa.add_final("1");
+        nfa.add_transitions(&[
+            ("0", "1", "a"),
+            ("1", "1", "a"),
+            ("0", "2", "b"),
+            ("1", "1", "b"),
+            ("2", "2", "a"),
+            ("2", "2", "b"),
+        ]);
+        nfa
+    }
+
+    #[test]
+    fn test_verify_winning_strategy() {
+        let nfa = sink_nfa();
+        let solution = solve(&nfa, &SolverOutput::Strategy);
+        assert!(solution.is_controllable);
+        assert_eq!(
+            verify_strategy(&nfa, &solution.winning_strategy),
+            VerificationResult::Winning
+        );
+    }
+
+    #[test]
+    fn test_verify_corrupted_strategy() {
+        let nfa = sink_nfa();
+        let winning = solve(&nfa, &SolverOutput::Strategy).winning_strategy;
+
+        // playing b everywhere sends the sheep of 0 to the sink
+        let mut corrupted = winning.clone();
+        corrupted.set_downset("b", DownSet::from_vecs(&[&[OMEGA; 3]]));
+        match verify_strategy(&nfa, &corrupted) {
+            VerificationResult::NotClosed { letter, ideal } => {
+                assert_eq!(letter, "b");
+                assert!(!winning.iter().any(|(a, downset)| a == "b" && downset.contains(&ideal)));
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+
+        assert_eq!(
+            verify_strategy(&nfa, &Strategy::empty()),
+            VerificationResult::NotDefinedOnInitial
+        );
+        let mut unknown = winning;
+        unknown.set_downset("c", DownSet::from_vecs(&[]));
+        assert_eq!(
+            verify_strategy(&nfa, &unknown),
+            VerificationResult::UnknownLetter("c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_minimize_strategy() {
+        // b is never needed, a alone wins
+        let mut nfa = sink_nfa();
+        nfa.add_transition("0", "0", "b");
+        let winning = solve(&nfa, &SolverOutput::Strategy).winning_strategy;
+        let nb_ideals = |strategy: &Strategy| -> usize {
+            strategy.iter().map(|(_, downset)| downset.ideals().count()).sum()
+        };
+
+        let mut minimized = winning.clone();
+        let source = get_omega_ideal(3, &[0]);
+        minimized.minimize_for_source(&source, &nfa.get_edges(), 3);
+        assert!(minimized.is_defined_on(&source));
+        assert!(nb_ideals(&minimized) <= nb_ideals(&winning));
+        assert_eq!(verify_strategy(&nfa, &minimized), VerificationResult::Winning);
+
+        let mut minimized = winning.clone();
+        assert!(minimize_winning_strategy(&nfa, &mut minimized, &SolverOptions::default()));
+        assert!(nb_ideals(&minimized) < nb_ideals(&winning));
+        assert_eq!(verify_strategy(&nfa, &minimized), VerificationResult::Winning);
+    }
+
+    #[test]
+    fn test_determinize_winning_controller_keeps_progress() {
+        // a loops everywhere and comes first, only b makes progress
+        let mut nfa = Nfa::from_states(&["p", "q"]);
+        nfa.add_initial("p");
+        nfa.add_final("q");
+        nfa.add_transitions(&[("p", "p", "a"), ("q", "q", "a"), ("p", "q", "b")]);
+        let winning = solve(&nfa, &SolverOutput::Strategy).winning_strategy;
+        assert_eq!(verify_strategy(&nfa, &winning), VerificationResult::Winning);
+
+        let mut determinized = winning.clone();
+        determinized.determinize_controller();
+        assert!(!verify_strategy(&nfa, &determinized).is_winning());
+
+        let mut strategy = winning.clone();
+        assert!(!determinize_winning_controller(&nfa, &mut strategy, &SolverOptions::default()));
+        assert_eq!(strategy, winning);
+    }
+
+    #[test]
+    fn test_initial_config() {
+        // a sheep sent from 0 by a must then be sent by b if it went to 1,
+        // and by a if it went to 2: one sheep can be controlled, two cannot
+        let nfa = Nfa::from_text(
+            "initial: 0
+0 a 1
+0 a 2
+0 b 0
+1 b 3
+1 a 4
+2 a 3
+2 b 4
+3 a 3
+3 b 3
+4 a 4
+4 b 4
+accepting: 3
+",
+        )
+        .unwrap();
+        assert!(!solve(&nfa, &SolverOutput::YesNo).is_controllable);
+        for (config, controllable) in [
+            ("1,0,0,0,0", true),
+            ("2,0,0,0,0", false),
+            ("0,1,1,0,0", false),
+        ] {
+            let options = SolverOptions {
+                initial_config: Some(config.parse().unwrap()),
+                ..SolverOptions::default()
+            };
+            for output in [SolverOutput::YesNo, SolverOutput::Strategy] {
+                let solution = try_solve(&nfa, &output, &options).unwrap();
+                assert_eq!(solution.is_controllable, controllable, "{}", config);
+            }
+        }
+
+        let options = SolverOptions {
+            initial_config: Some("1,0".parse().unwrap()),
+            ..SolverOptions::default()
+        };
+        assert_eq!(
+            try_solve(&nfa, &SolverOutput::YesNo, &options).err(),
+            Some(SolverError::InvalidInitialConfig {
+                expected: 5,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_bruteforce_agrees_on_examples() {
+        for (name, content, expected) in crate::examples::EXAMPLES {
+            let nfa = Nfa::from_tikz(content);
+            let bound = nfa.nb_states() as coef;
+            assert_eq!(solve_bruteforce(&nfa, bound), *expected, "{}", name);
+            assert_eq!(
+                solve(&nfa, &SolverOutput::YesNo).is_controllable,
+                *expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_bruteforce_initial_config() {
+        // same automaton as test_initial_config: one sheep is fine, two are not
+        let mut nfa = Nfa::from_states(&["0", "1", "2", "3", "4"]);
+        nfa.add_initial("0");
+        nfa.add_final("3");
+        nfa.add_transitions(&[
+            ("0", "1", "a"),
+            ("0", "2", "a"),
+            ("1", "3", "b"),
+            ("1", "4", "a"),
+            ("2", "3", "a"),
+            ("2", "4", "b"),
+            ("3", "3", "a"),
+            ("3", "3", "b"),
+            ("4", "4", "a"),
+            ("4", "4", "b"),
+        ]);
+        assert!(solve_bruteforce(&nfa, 1));
+        assert!(!solve_bruteforce(&nfa, 2));
+        assert!(!solve(&nfa, &SolverOutput::YesNo).is_controllable);
+    }
+
+    #[test]
+    fn test_solve_trace() {
+        let nfa = Nfa::from_tikz(crate::examples::EXAMPLE2);
+        for output in [SolverOutput::Strategy, SolverOutput::YesNo] {
+            let (solution, trace) = solve_trace(&nfa, &output);
+            assert!(trace.len() > 1);
+            assert_eq!(trace.last(), Some(&solution.winning_strategy));
+            for window in trace.windows(2) {
+                let (before, after) = (&window[0], &window[1]);
+                for (a, downset) in after.iter() {
+                    let (_, previous) = before.iter().find(|(b, _)| *b == a).unwrap();
+                    assert!(downset.is_contained_in(previous));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nondeterminism_warning_lists_pairs() {
+        let mut nfa = Nfa::from_states(&["p", "q", "r"]);
+        nfa.add_transitions(&[("p", "q", "a"), ("p", "r", "a"), ("p", "p", "b"), ("q", "r", "b")]);
+        nfa.add_initial("p");
+        nfa.add_final("r");
+        assert_eq!(
+            nondeterminism_warning(&nfa),
+            Some("The automaton is not deterministic, several successors for (p, a)".to_string())
+        );
+        nfa.add_transition("q", "p", "b");
+        assert!(nondeterminism_warning(&nfa).unwrap().ends_with("(p, a), (q, b)"));
+
+        let mut deterministic = Nfa::from_states(&["p", "q"]);
+        deterministic.add_transitions(&[("p", "q", "a"), ("q", "q", "a")]);
+        deterministic.add_initial("p");
+        deterministic.add_initial("q");
+        assert_eq!(nondeterminism_warning(&deterministic), None);
+
+        // purely diagnostic
+        let options = SolverOptions {
+            warn_on_nondeterminism: true,
+            ..SolverOptions::default()
+        };
+        assert_eq!(
+            solve_with_options(&nfa, &SolverOutput::YesNo, &options).is_controllable,
+            solve(&nfa, &SolverOutput::YesNo).is_controllable
+        );
+    }
+
+    #[test]
+    fn solve_all_matches_solve() {
+        let nfas = crate::examples::EXAMPLES
+            .iter()
+            .map(|(_, content, _)| Nfa::from_tikz(content))
+            .collect::<Vec<_>>();
+        let expected = nfas
+            .iter()
+            .map(|nfa| solve(nfa, &SolverOutput::YesNo).is_controllable)
+            .collect::<Vec<_>>();
+        let answers = |solutions: Vec<Solution>| {
+            solutions.iter().map(|s| s.is_controllable).collect::<Vec<_>>()
+        };
+        assert_eq!(answers(solve_all(&nfas, &SolverOutput::YesNo)), expected);
+        let serial = SolverOptions {
+            serial: true,
+            ..SolverOptions::default()
+        };
+        assert_eq!(
+            answers(solve_all_with_options(&nfas, &SolverOutput::YesNo, &serial)),
+            expected
+        );
+        assert!(solve_all(&[], &SolverOutput::YesNo).is_empty());
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn arbitrary_automata_solve() {
+        use arbitrary::{Arbitrary, Unstructured};
+        //a fixed linear congruential generator keeps the test reproducible
+        let mut seed: u64 = 0x5eed;
+        let mut bytes = vec![0u8; 256];
+        for _ in 0..200 {
+            for byte in bytes.iter_mut() {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                *byte = (seed >> 56) as u8;
+            }
+            let nfa = Nfa::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            let options = SolverOptions::default();
+            match (
+                try_solve(&nfa, &SolverOutput::YesNo, &options),
+                try_solve(&nfa, &SolverOutput::Strategy, &options),
+            ) {
+                (Ok(yes_no), Ok(strategy)) => {
+                    assert!(nfa.alphabet_size() > 0, "{}", nfa);
+                    assert_eq!(yes_no.is_controllable, strategy.is_controllable, "{}", nfa);
+                }
+                (Err(SolverError::EmptyAlphabet), Err(SolverError::EmptyAlphabet)) => {
+                    assert_eq!(nfa.alphabet_size(), 0, "{}", nfa)
+                }
+                (yes_no, strategy) => panic!(
+                    "unexpected results {:?} and {:?} on {}",
+                    yes_no.err(),
+                    strategy.err(),
+                    nfa
+                ),
+            }
+        }
+    }
 }
@@ -1,10 +1,10 @@
-use crate::coef::{coef, OMEGA};
+use crate::coef::{coef, C0, OMEGA};
 use crate::downset::DownSet;
 use crate::graph::Graph;
 use crate::ideal::Ideal;
 use crate::nfa;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 
 /// A strategy is a map from letters to downsets, possibly empty.
@@ -25,6 +25,27 @@ impl Strategy {
         )
     }
 
+    /// The strategy playing no letter at all.
+    pub fn empty() -> Self {
+        Strategy(HashMap::new())
+    }
+
+    /// True if no letter can be played anywhere.
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(|downset| downset.is_empty())
+    }
+
+    /// The configurations from which some letter is playable,
+    /// i.e. the union of the downsets of all letters.
+    pub fn winning_region(&self) -> DownSet {
+        let mut region = DownSet::from_vecs(&[]);
+        for ideal in self.0.values().flat_map(|downset| downset.ideals()) {
+            region.insert(ideal);
+        }
+        region.minimize();
+        region
+    }
+
     pub fn is_defined_on(&self, source: &Ideal) -> bool {
         self.0.values().any(|downset| downset.contains(source))
     }
@@ -32,7 +53,7 @@ impl Strategy {
     pub fn restrict_to(
         &mut self,
         safe: DownSet,
-        edges_per_letter: &HashMap<nfa::Letter, Graph>,
+        edges_per_letter: &BTreeMap<nfa::Letter, Graph>,
         maximal_finite_value: coef,
     ) -> bool {
         let mut result = false;
@@ -46,22 +67,186 @@ impl Strategy {
         result
     }
 
+    /// Greedily remove letters, then single ideals, as long as the strategy
+    /// stays defined on `source` and closed: playing a letter from one of its ideals
+    /// must lead to configurations where the strategy is still defined.
+    /// Ideals which were not closed beforehand, e.g. those leading to accepting
+    /// configurations where no letter is needed, are tolerated.
+    /// Progress towards the accepting states is not checked again,
+    /// see `solver::verify_strategy`.
+    /// Returns true if something was removed.
+    pub fn minimize_for_source(
+        &mut self,
+        source: &Ideal,
+        edges_per_letter: &BTreeMap<nfa::Letter, Graph>,
+        maximal_finite_value: coef,
+    ) -> bool {
+        let not_closed = self.not_closed(edges_per_letter, maximal_finite_value);
+        let is_acceptable = |candidate: &Strategy| {
+            candidate.is_defined_on(source)
+                && candidate
+                    .not_closed(edges_per_letter, maximal_finite_value)
+                    .is_subset(&not_closed)
+        };
+        let mut changed = false;
+        let letters = self.letters().into_iter().cloned().collect::<Vec<_>>();
+        for letter in letters.iter() {
+            let mut candidate = self.clone();
+            candidate.0.remove(letter);
+            if is_acceptable(&candidate) {
+                *self = candidate;
+                changed = true;
+            }
+        }
+        let pairs = self
+            .letters()
+            .into_iter()
+            .flat_map(|a| self.0[a].iter_sorted().map(move |ideal| (a.clone(), ideal.clone())))
+            .collect::<Vec<_>>();
+        for (letter, ideal) in pairs {
+            let mut candidate = self.clone();
+            candidate
+                .0
+                .get_mut(&letter)
+                .unwrap()
+                .retain(|other| *other != ideal);
+            if is_acceptable(&candidate) {
+                *self = candidate;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    //the pairs (letter, ideal) from which playing the letter may leave the winning region
+    fn not_closed(
+        &self,
+        edges_per_letter: &BTreeMap<nfa::Letter, Graph>,
+        maximal_finite_value: coef,
+    ) -> HashSet<(nfa::Letter, Ideal)> {
+        let region = self.winning_region();
+        let mut result = HashSet::new();
+        for (a, downset) in self.0.iter() {
+            let safe = region.safe_pre_image(edges_per_letter.get(a).unwrap(), maximal_finite_value);
+            for ideal in downset.ideals().filter(|&ideal| !safe.contains(ideal)) {
+                result.insert((a.clone(), ideal.clone()));
+            }
+        }
+        result
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (&nfa::Letter, &DownSet)> {
         self.0.iter()
     }
 
-    // create a CSV representation of this strategy.
+    /// The letters the strategy is defined for, sorted.
+    pub fn letters(&self) -> Vec<&nfa::Letter> {
+        let mut letters = self.0.keys().collect::<Vec<_>>();
+        letters.sort();
+        letters
+    }
+
+    /// Forget the letters which are not in `allowed`,
+    /// the strategy becomes undefined on them.
+    pub fn restrict_alphabet(&mut self, allowed: &[&str]) {
+        self.0.retain(|a, _| allowed.contains(&a.as_str()));
+    }
+
+    /// The strategy playing a letter wherever one of the two strategies plays it.
+    /// Panics if the downsets of a letter have different dimensions.
+    pub fn merge(&self, other: &Strategy) -> Strategy {
+        let mut merged = self.clone();
+        for (letter, downset) in other.0.iter() {
+            let union = match self.0.get(letter) {
+                Some(own) => own.union(downset),
+                None => downset.union(&DownSet::from_vecs(&[])),
+            };
+            merged.0.insert(letter.clone(), union);
+        }
+        merged
+    }
+
+    /// True if no configuration with at least one sheep is in the downsets of two letters,
+    /// i.e. the controller never has to choose. The empty configuration is in every downset.
+    pub fn is_deterministic_controller(&self) -> bool {
+        let letters = self.letters();
+        letters.iter().enumerate().all(|(i, a)| {
+            letters[i + 1..].iter().all(|b| {
+                self.0[*a].ideals().all(|x| {
+                    self.0[*b]
+                        .ideals()
+                        .all(|y| Ideal::intersection(x, y).is_bottom())
+                })
+            })
+        })
+    }
+
+    /// Letters get priority in alphabetical order: an ideal of a letter is dropped
+    /// if it is included in the downset of a letter with higher priority,
+    /// which is then played there instead. The domain of the strategy is unchanged,
+    /// but the letters left may make no progress, see `solver::determinize_winning_controller`.
+    /// Overlaps which downsets cannot separate remain, see `is_deterministic_controller`.
+    pub fn determinize_controller(&mut self) {
+        let mut covered = DownSet::from_vecs(&[]);
+        for letter in self.letters().into_iter().cloned().collect::<Vec<_>>() {
+            let downset = self.0.get_mut(&letter).unwrap();
+            downset.retain(|ideal| !covered.contains(ideal));
+            covered = covered.union(downset);
+        }
+    }
+
+    /// Play `letter` exactly on `downset`, e.g. to load a strategy computed elsewhere.
+    pub fn set_downset(&mut self, letter: &str, downset: DownSet) {
+        self.0.insert(letter.to_string(), downset);
+    }
+
+    // create a CSV representation of this strategy, letters in alphabetical order.
     pub fn as_csv(&self) -> String {
         let mut lines: Vec<nfa::Letter> = Vec::new();
 
-        for (a, i) in &self.0 {
-            for s in i.as_csv() {
+        for a in self.letters() {
+            for s in self.0.get(a).unwrap().as_csv() {
                 let l = format!("{a},{s}");
                 lines.push(l);
             }
         }
         lines.join("\n")
     }
+
+    /// Human-readable form naming the states, one line per ideal,
+    /// listing only nonzero coordinates, e.g. `a: { ini:2, barn:ω }`.
+    /// Letters are in alphabetical order.
+    pub fn to_string_with_states(&self, states: &[&str]) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for a in self.letters() {
+            let downset = self.0.get(a).unwrap();
+            if downset.is_empty() {
+                lines.push(format!("{}: never", a));
+            }
+            for ideal in downset.iter_sorted() {
+                let coordinates = ideal
+                    .iter()
+                    .zip(states.iter())
+                    .filter(|(&c, _)| c != C0)
+                    .map(|(c, q)| format!("{}:{}", q, c))
+                    .collect::<Vec<_>>();
+                lines.push(format!("{}: {{ {} }}", a, coordinates.join(", ")));
+            }
+        }
+        lines.join("\n")
+    }
+
+    // create a CSV representation of this strategy,
+    // with a header row naming the states, and letters in alphabetical order.
+    pub fn as_csv_with_states(&self, states: &[&str]) -> String {
+        let mut lines: Vec<String> = vec![format!("letter,{}", states.join(","))];
+        for a in self.letters() {
+            for s in self.0.get(a).unwrap().as_csv() {
+                lines.push(format!("{a},{s}"));
+            }
+        }
+        lines.join("\n")
+    }
 }
 
 impl fmt::Display for Strategy {
@@ -90,6 +275,7 @@ impl fmt::Display for Strategy {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::coef::C1;
     use crate::ideal::Ideal;
 
     #[test]
@@ -107,4 +293,104 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_merge() {
+        let partial = Strategy(HashMap::from([
+            ('a'.to_string(), DownSet::from_vecs(&[&[OMEGA, C0]])),
+            ('b'.to_string(), DownSet::from_vecs(&[&[C0, OMEGA], &[C0, C0]])),
+        ]));
+        let maximal = Strategy::get_maximal_strategy(2, &["a", "b"]);
+        let mut minimized = partial.clone();
+        minimized.0.values_mut().for_each(|downset| {
+            downset.minimize();
+        });
+        assert_eq!(partial.merge(&partial), minimized);
+        assert_eq!(partial.merge(&maximal), maximal);
+        assert_eq!(maximal.merge(&partial), maximal);
+
+        let only_c = Strategy(HashMap::from([(
+            'c'.to_string(),
+            DownSet::from_vecs(&[&[C0, OMEGA]]),
+        )]));
+        let merged = partial.merge(&only_c);
+        assert_eq!(merged.letters(), ["a", "b", "c"]);
+        assert_eq!(merged.0["c"], only_c.0["c"]);
+    }
+
+    #[test]
+    fn test_determinize_controller() {
+        let mut overlapping = Strategy(HashMap::from([
+            ('a'.to_string(), DownSet::from_vecs(&[&[OMEGA, C1], &[C0, OMEGA]])),
+            ('b'.to_string(), DownSet::from_vecs(&[&[OMEGA, C0], &[C1, C1]])),
+        ]));
+        assert!(!overlapping.is_deterministic_controller());
+        let region = overlapping.winning_region();
+        overlapping.determinize_controller();
+        assert!(overlapping.is_deterministic_controller());
+        assert_eq!(overlapping.0["a"], DownSet::from_vecs(&[&[OMEGA, C1], &[C0, OMEGA]]));
+        assert!(overlapping.0["b"].is_empty());
+        assert_eq!(overlapping.winning_region(), region);
+
+        let disjoint = Strategy(HashMap::from([
+            ('a'.to_string(), DownSet::from_vecs(&[&[OMEGA, C0]])),
+            ('b'.to_string(), DownSet::from_vecs(&[&[C0, OMEGA]])),
+        ]));
+        assert!(disjoint.is_deterministic_controller());
+    }
+
+    #[test]
+    fn test_winning_region() {
+        let mut strategy = Strategy::get_maximal_strategy(2, &["a", "b"]);
+        assert!(!strategy.is_empty());
+        assert_eq!(
+            strategy.winning_region(),
+            DownSet::from_vecs(&[&[OMEGA, OMEGA]])
+        );
+
+        let partial = Strategy(HashMap::from([
+            ('a'.to_string(), DownSet::from_vecs(&[&[OMEGA, C0]])),
+            ('b'.to_string(), DownSet::from_vecs(&[&[C0, OMEGA], &[C0, C0]])),
+        ]));
+        assert_eq!(
+            partial.winning_region(),
+            DownSet::from_vecs(&[&[OMEGA, C0], &[C0, OMEGA]])
+        );
+
+        for downset in strategy.0.values_mut() {
+            downset.restrict_to(&DownSet::from_vecs(&[]));
+        }
+        assert!(strategy.is_empty());
+        assert!(strategy.winning_region().is_empty());
+        assert!(Strategy::empty().is_empty());
+    }
+
+    #[test]
+    fn test_as_csv_with_states() {
+        let strategy = Strategy(HashMap::from([
+            ('b'.to_string(), DownSet::from_vecs(&[&[C0, OMEGA]])),
+            ('a'.to_string(), DownSet::from_vecs(&[&[OMEGA, C0]])),
+        ]));
+        let csv = strategy.as_csv_with_states(&["q1", "q0"]);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "letter,q1,q0");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("a,"));
+        assert!(lines[2].starts_with("b,"));
+    }
+
+    #[test]
+    fn test_restrict_alphabet() {
+        let mut strategy = Strategy(HashMap::from([
+            ('a'.to_string(), DownSet::from_vecs(&[&[OMEGA, C0]])),
+            ('b'.to_string(), DownSet::from_vecs(&[&[C0, OMEGA]])),
+        ]));
+        let source = Ideal::from_vec(vec![C0, OMEGA]);
+        assert_eq!(strategy.letters(), vec!["a", "b"]);
+        assert!(strategy.is_defined_on(&source));
+        strategy.restrict_alphabet(&["a", "c"]);
+        assert_eq!(strategy.letters(), vec!["a"]);
+        assert!(!strategy.is_defined_on(&source));
+        assert!(strategy.is_defined_on(&Ideal::from_vec(vec![OMEGA, C0])));
+    }
 }
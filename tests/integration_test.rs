@@ -3,11 +3,9 @@ use shepherd::solver;
 use shepherd::coef::{C0, C1, C2, OMEGA};
 use shepherd::downset::DownSet;
 use shepherd::ideal::Ideal;
-
-const EXAMPLE1: &str = include_str!("../examples/bottleneck-1-ab.tikz");
-const EXAMPLE1_COMPLETE: &str = include_str!("../examples/bottleneck-1-ab-complete.tikz");
-const EXAMPLE2: &str = include_str!("../examples/bottleneck-2.tikz");
-const EXAMPLE_BUG12: &str = include_str!("../examples/bug12.tikz");
+use shepherd::solution::Certificate;
+use shepherd::examples::{CONTROLLABLE, EXAMPLE1, EXAMPLE1_COMPLETE, EXAMPLE2, EXAMPLE_BUG12};
+use std::process::Command;
 
 #[test]
 fn test_example_1() {
@@ -33,9 +31,9 @@ fn test_example_1() {
 
     assert_eq!(
         *downseta,
-        DownSet::from_vecs(&[&[C1, C0, C0, C0, C0], &[C0, OMEGA, C0, C0, C0]])
+        DownSet::from_vecs(&[&[C1, C0, C0, C0], &[C0, OMEGA, C0, C0]])
     );
-    assert_eq!(*downsetb, DownSet::from_vecs(&[&[C0, C0, OMEGA, C0, C0]]));
+    assert_eq!(*downsetb, DownSet::from_vecs(&[&[C0, C0, OMEGA, C0]]));
 }
 
 #[test]
@@ -70,6 +68,78 @@ fn test_example_1bis() {
     );
 }
 
+#[test]
+fn test_example_1_completed() {
+    // not controllable, with or without a sink for the missing edges
+    let mut nfa = nfa::Nfa::from_tikz(EXAMPLE1);
+    assert!(!solver::solve(&nfa, &solver::SolverOutput::YesNo).is_controllable);
+    let sink = nfa.complete_with_sink().unwrap();
+    assert!(nfa.is_complete());
+    let solution = solver::solve(&nfa, &solver::SolverOutput::YesNo);
+    assert!(!solution.is_controllable);
+    // no token may ever be sent to the sink
+    for (_, downset) in solution.winning_strategy.iter() {
+        assert!(downset.ideals().all(|ideal| ideal.get(sink) == C0));
+    }
+}
+
+#[test]
+fn test_completed_controllable() {
+    // the b-transition from 2 rescues the tokens left behind by the first a
+    let mut nfa = nfa::Nfa::from_states(&["0", "1", "2"]);
+    nfa.add_initial("0");
+    nfa.add_final("1");
+    nfa.add_transitions(&[
+        ("0", "1", "a"),
+        ("0", "2", "a"),
+        ("1", "1", "a"),
+        ("1", "1", "b"),
+        ("2", "1", "b"),
+    ]);
+    assert!(solver::solve(&nfa, &solver::SolverOutput::YesNo).is_controllable);
+    nfa.complete_with_sink();
+    assert!(solver::solve(&nfa, &solver::SolverOutput::YesNo).is_controllable);
+}
+
+#[test]
+fn test_completion_modes_differ() {
+    // the b needed to move the sheep from 2 to 1 is missing on 1
+    let incomplete = || {
+        let mut nfa = nfa::Nfa::from_states(&["0", "1", "2"]);
+        nfa.add_initial("0");
+        nfa.add_final("1");
+        nfa.add_transitions(&[("0", "1", "a"), ("0", "2", "a"), ("2", "1", "b")]);
+        nfa
+    };
+    let mut with_sink = incomplete();
+    with_sink.complete_with_sink();
+    assert!(with_sink.is_complete());
+    assert!(!solver::solve(&with_sink, &solver::SolverOutput::YesNo).is_controllable);
+
+    let mut with_loops = incomplete();
+    with_loops.complete(nfa::CompletionMode::SelfLoop);
+    assert!(with_loops.is_complete());
+    assert_eq!(with_loops.nb_states(), 3);
+    assert!(solver::solve(&with_loops, &solver::SolverOutput::YesNo).is_controllable);
+}
+
+#[test]
+fn test_input_was_complete() {
+    let solution = solver::solve(&nfa::Nfa::from_tikz(EXAMPLE1), &solver::SolverOutput::YesNo);
+    assert!(!solution.input_was_complete);
+    assert!(solution.to_string().contains("missing transitions are treated as loss"));
+
+    // the sink of the "complete" variant has no outgoing transition
+    let mut nfa = nfa::Nfa::from_tikz(EXAMPLE1_COMPLETE);
+    let solution = solver::solve(&nfa, &solver::SolverOutput::YesNo);
+    assert!(!solution.input_was_complete);
+    nfa.complete(nfa::CompletionMode::SelfLoop);
+    let completed = solver::solve(&nfa, &solver::SolverOutput::YesNo);
+    assert!(completed.input_was_complete);
+    assert!(!completed.to_string().contains("missing transitions"));
+    assert_eq!(completed.is_controllable, solution.is_controllable);
+}
+
 #[test]
 fn test_example_2() {
     let nfa = nfa::Nfa::from_tikz(EXAMPLE2);
@@ -139,3 +209,311 @@ fn test_bug12() {
     println!("{}", downsetb);
     assert!(downsetb.contains(&Ideal::from_vec(vec![C2, C0, C0, C0, C0, C0, C0, C0])));
 }
+
+fn run_shepherd(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_shepherd"))
+        .args(args)
+        .output()
+        .expect("could not run shepherd")
+}
+
+#[test]
+fn test_solver_output_both() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/bottleneck-2.tikz");
+    let output = run_shepherd(&["--solver-output", "both", "--to", "plain", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("NO (uncontrollable)").count(), 1);
+    assert_eq!(stdout.matches("a: { i:2 }").count(), 1);
+    assert!(!stdout.contains("Play action"));
+}
+
+#[test]
+fn test_output_is_deterministic() {
+    // hash sets are seeded differently in every process
+    let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    for (input_format, name) in [("tikz", "bottleneck-2.tikz"), ("dot", "bottleneck-1-ab.dot")] {
+        let path = examples.join(name);
+        let path = path.to_str().unwrap();
+        for format in ["plain", "csv"] {
+            let args = ["--solver-output", "both", "-f", input_format, "--to", format, path];
+            let first = run_shepherd(&args);
+            assert!(first.status.success());
+            for _ in 0..3 {
+                assert_eq!(run_shepherd(&args).stdout, first.stdout, "{} {}", name, format);
+            }
+            // the flag is a no-op, kept for scripts passing it
+            let flagged: Vec<&str> = std::iter::once("--deterministic").chain(args).collect();
+            let with_flag = run_shepherd(&flagged);
+            assert!(with_flag.status.success());
+            assert_eq!(with_flag.stdout, first.stdout, "{} {} --deterministic", name, format);
+        }
+    }
+}
+
+#[test]
+fn test_batch_rejects_single_automaton_flags() {
+    let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let inputs = ["bottleneck-1-ab.tikz", "bottleneck-2.tikz"]
+        .map(|name| examples.join(name).to_str().unwrap().to_string());
+    for flag in ["--profile", "--minimize-strategy", "--parse-only", "--determinize-controller"] {
+        let output = run_shepherd(&[flag, &inputs[0], &inputs[1]]);
+        assert_eq!(output.status.code(), Some(2), "{}", flag);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(flag));
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+fn test_output_dir() {
+    let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let inputs = ["bottleneck-1-ab.tikz", "bottleneck-2.tikz"]
+        .map(|name| examples.join(name).to_str().unwrap().to_string());
+    let dir = std::env::temp_dir().join(format!("shepherd-output-dir-{}", std::process::id()));
+    let output = run_shepherd(&[
+        "--to",
+        "csv",
+        "--output-dir",
+        dir.to_str().unwrap(),
+        &inputs[0],
+        &inputs[1],
+    ]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+    for (name, input) in ["bottleneck-1-ab", "bottleneck-2"].iter().zip(inputs.iter()) {
+        let nfa = nfa::Nfa::from_tikz(&std::fs::read_to_string(input).unwrap());
+        let solution = solver::solve(&nfa, &solver::SolverOutput::Strategy);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(format!("{}.csv", name))).unwrap(),
+            format!("{}\n", solution.winning_strategy.as_csv_with_states(&nfa.states()))
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // as on stdout, no strategy is written for a negative yes/no answer
+    let args = ["--solver-output", "yes-no", "--to", "csv", "--output-dir"];
+    let output = run_shepherd(&[&args[..], &[dir.to_str().unwrap(), &inputs[1]]].concat());
+    assert!(output.status.success());
+    assert!(!dir.join("bottleneck-2.csv").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // the same stem in two directories
+    let inputs_dir = dir.with_extension("inputs");
+    for sub in ["a", "b"] {
+        std::fs::create_dir_all(inputs_dir.join(sub)).unwrap();
+        std::fs::copy(&inputs[1], inputs_dir.join(sub).join("x.tikz")).unwrap();
+    }
+    let output = run_shepherd(&[
+        "--output-dir",
+        dir.to_str().unwrap(),
+        inputs_dir.join("a/x.tikz").to_str().unwrap(),
+        inputs_dir.join("b/x.tikz").to_str().unwrap(),
+    ]);
+    std::fs::remove_dir_all(&inputs_dir).unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("would both be written"));
+    assert!(!dir.exists());
+}
+
+fn run_quiet(path: &std::path::Path) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_shepherd"))
+        .arg("--quiet")
+        .arg(path)
+        .output()
+        .expect("could not run shepherd")
+}
+
+#[test]
+fn test_quiet_exit_status() {
+    let controllable = std::env::temp_dir().join("shepherd-quiet-controllable.tikz");
+    std::fs::write(&controllable, CONTROLLABLE).unwrap();
+    let output = run_quiet(&controllable);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let uncontrollable =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/bottleneck-1-ab.tikz");
+    let output = run_quiet(&uncontrollable);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_bundled_examples() {
+    for (name, content, expected) in shepherd::examples::EXAMPLES {
+        let nfa = nfa::Nfa::from_tikz(content);
+        let solution = solver::solve(&nfa, &solver::SolverOutput::YesNo);
+        assert_eq!(solution.is_controllable, *expected, "{}", name);
+    }
+}
+
+#[test]
+fn test_serial_matches_parallel() {
+    let serial = solver::SolverOptions {
+        serial: true,
+        ..Default::default()
+    };
+    for (name, content, _) in shepherd::examples::EXAMPLES {
+        let nfa = nfa::Nfa::from_tikz(content);
+        for output in [solver::SolverOutput::YesNo, solver::SolverOutput::Strategy] {
+            let parallel = solver::solve(&nfa, &output);
+            let single = solver::solve_with_options(&nfa, &output, &serial);
+            assert_eq!(single.is_controllable, parallel.is_controllable, "{}", name);
+            assert_eq!(single.winning_strategy, parallel.winning_strategy, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn test_solution_output_is_deterministic() {
+    let render = || {
+        let nfa = nfa::Nfa::from_tikz(EXAMPLE2);
+        let solution = solver::solve(&nfa, &solver::SolverOutput::Strategy);
+        format!(
+            "{}{}{}{}{}",
+            solution,
+            solution.winning_strategy,
+            solution.winning_strategy.as_csv_with_states(&nfa.states()),
+            solution.semigroup,
+            nfa
+        )
+    };
+    let first = render();
+    for _ in 0..3 {
+        assert_eq!(render(), first);
+    }
+}
+
+#[test]
+fn test_losing_states() {
+    let nfa = nfa::Nfa::from_tikz(include_str!("../examples/bottleneck-2.tikz"));
+    let solution = solver::solve(&nfa, &solver::SolverOutput::Strategy);
+    assert!(!solution.is_controllable);
+    // each middle state alone can be sent to f, but not the three of them at once
+    assert_eq!(solution.losing_states(), vec!["i"]);
+
+    let nfa = nfa::Nfa::from_tikz(CONTROLLABLE);
+    let solution = solver::solve(&nfa, &solver::SolverOutput::Strategy);
+    assert!(solution.is_controllable);
+    assert!(solution.losing_states().is_empty());
+}
+
+#[test]
+fn test_strategy_with_state_names() {
+    let nfa = nfa::Nfa::from_tikz(include_str!("../examples/bottleneck-2.tikz"));
+    let solution = solver::solve(&nfa, &solver::SolverOutput::Strategy);
+    let named = solution.winning_strategy.to_string_with_states(&nfa.states());
+    assert_eq!(
+        named,
+        "a: { i:2 }\nb: { 1:ω, 3:ω }\nc: { 1:ω, 2:ω }\nd: { 2:ω, 3:ω }"
+    );
+}
+
+#[test]
+fn test_markdown_report() {
+    let nfa = nfa::Nfa::from_tikz(include_str!("../examples/bottleneck-2.tikz"));
+    let solution = solver::solve(&nfa, &solver::SolverOutput::Strategy);
+    let report = solution.as_markdown();
+    for heading in ["# Population control", "## Automaton", "## Answer", "## Strategy"] {
+        assert!(report.lines().any(|line| line == heading), "{}", heading);
+    }
+    assert!(report.contains("\n**NO (uncontrollable)**\n"));
+    assert!(report.contains("### Letter `a`"));
+    assert!(report.contains("| State | Initial | Accepting |"));
+    assert!(report.contains("| i | yes | no |"));
+    let states = nfa.states();
+    assert!(report.contains(&format!("| {} |", states.join(" | "))));
+}
+
+#[test]
+fn test_summary_line() {
+    let nfa = nfa::Nfa::from_tikz(EXAMPLE1);
+    let solution = solver::solve(&nfa, &solver::SolverOutput::YesNo);
+    let line = solution.summary_line("bottleneck-1-ab.tikz");
+    let fields: Vec<&str> = line.split('\t').collect();
+    assert_eq!(fields.len(), 6);
+    assert_eq!(fields[0], "bottleneck-1-ab.tikz");
+    assert_eq!(fields[1], "CONTROLLABLE=false");
+    assert_eq!(fields[2], format!("STATES={}", nfa.nb_states()));
+    assert_eq!(fields[3], "LETTERS=2");
+    assert_eq!(fields[4], format!("SEMIGROUP={}", solution.semigroup.len()));
+    let ms = fields[5].strip_prefix("MS=").unwrap();
+    assert!(ms.parse::<u128>().is_ok());
+    assert!(!line.contains('\n'));
+}
+
+#[test]
+fn test_certificates() {
+    let nfa = nfa::Nfa::from_tikz(CONTROLLABLE);
+    let solution = solver::solve(&nfa, &solver::SolverOutput::Strategy);
+    let certificate = solution.certificate();
+    let Certificate::Controllable { strategy, source, run } = &certificate else {
+        panic!("expected a strategy, got {:?}", certificate);
+    };
+    assert!(!run.is_empty());
+    assert!(certificate.verify(&nfa));
+    // a run which stops before the accepting states proves nothing
+    let truncated = Certificate::Controllable {
+        strategy: strategy.clone(),
+        source: source.clone(),
+        run: run[..run.len() - 1].to_vec(),
+    };
+    assert!(!truncated.verify(&nfa));
+
+    let nfa = nfa::Nfa::from_tikz(EXAMPLE1_COMPLETE);
+    let solution = solver::solve(&nfa, &solver::SolverOutput::YesNo);
+    let certificate = solution.certificate();
+    let Certificate::LimitWitness { witnesses } = &certificate else {
+        panic!("expected flows, got {:?}", certificate);
+    };
+    assert_eq!(witnesses.len(), solution.sources.len());
+    assert!(certificate.verify(&nfa));
+    for (source, flow) in witnesses {
+        assert!(!source.is_below(&flow.pre_image(&nfa.final_states())));
+    }
+    // the witness of one automaton does not fit another
+    assert!(!certificate.verify(&nfa::Nfa::from_tikz(EXAMPLE2)));
+}
+
+#[test]
+fn test_certificates_follow_source_semantics() {
+    // sheep starting on r are stuck, those starting on p are not
+    let mut nfa = nfa::Nfa::from_states(&["p", "r", "f"]);
+    nfa.add_transitions(&[("p", "f", "a"), ("r", "r", "b"), ("f", "f", "a")]);
+    nfa.add_initial("p");
+    nfa.add_initial("r");
+    nfa.add_final("f");
+
+    let options = solver::SolverOptions {
+        source_semantics: solver::SourceSemantics::AnyInitial,
+        ..Default::default()
+    };
+    let solution = solver::solve_with_options(&nfa, &solver::SolverOutput::Strategy, &options);
+    assert!(solution.is_controllable);
+    assert_eq!(solution.sources.len(), 2);
+    let certificate = solution.certificate();
+    let Certificate::Controllable { source, .. } = &certificate else {
+        panic!("expected a strategy, got {:?}", certificate);
+    };
+    assert_eq!(*source, Ideal::from_vec(vec![OMEGA, C0, C0]));
+    assert!(certificate.verify(&nfa));
+
+    let solution = solver::solve(&nfa, &solver::SolverOutput::Strategy);
+    assert!(!solution.is_controllable);
+    assert!(!matches!(solution.certificate(), Certificate::Controllable { .. }));
+}
+
+#[test]
+fn test_schaeppert_fails_without_prism() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/bottleneck-2.tikz");
+    let dir = std::env::temp_dir().join(format!("shepherd-prism-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_schaeppert"))
+        .args(["--prism-cmd", "/nonexistent/prism", path.to_str().unwrap()])
+        .arg(&dir)
+        .output()
+        .expect("could not run schaeppert");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error calling prism"));
+}